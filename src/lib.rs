@@ -0,0 +1,503 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Minimum and maximum number of issues each member can get.
+#[derive(Debug, Clone, Copy)]
+pub struct MemberLimits {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl MemberLimits {
+    /// Errors if no distribution of `issue_count` issues with `per_issue` assignees each between
+    /// `member_count` members can respect the limits.
+    pub fn check(
+        &self,
+        issue_count: usize,
+        member_count: usize,
+        per_issue: usize,
+    ) -> Result<(), String> {
+        let slot_count = issue_count * per_issue;
+        let needed = if per_issue == 1 {
+            format!("{} issues selected", issue_count)
+        } else {
+            format!("{} assignments needed", slot_count)
+        };
+
+        if let Some(max) = self.max {
+            if max < self.min {
+                return Err(format!(
+                    "--max-per-member ({}) is lower than --min-per-member ({})",
+                    max, self.min
+                ));
+            }
+            if member_count * max < slot_count {
+                return Err(format!(
+                    "{} members × max {} = {} slots but {}",
+                    member_count,
+                    max,
+                    member_count * max,
+                    needed
+                ));
+            }
+        }
+        if member_count * self.min > slot_count {
+            return Err(format!(
+                "{} members × min {} = {} slots but {}",
+                member_count,
+                self.min,
+                member_count * self.min,
+                needed
+            ));
+        }
+        // a member can only be assigned once to each issue
+        if self.min > issue_count {
+            return Err(format!(
+                "a member cannot get {} issues when only {} are selected",
+                self.min, issue_count
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Randomly assigns `per_issue` distinct members to each issue, every member gets the same number
+/// of issues, give or take one.
+///
+/// Returns, for each issue, the indices of the members it is assigned to.
+pub fn distribute(
+    issue_count: usize,
+    member_count: usize,
+    per_issue: usize,
+    rng: &mut impl Rng,
+) -> Vec<Vec<usize>> {
+    let mut issue_order: Vec<usize> = (0..issue_count).collect();
+    issue_order.shuffle(rng);
+    deal_least_loaded(&issue_order, member_count, per_issue, rng)
+}
+
+// gives each issue, in the given order, the `per_issue` members with the fewest issues so far, ties
+// broken randomly so that the same members do not always end up together
+fn deal_least_loaded(
+    issue_order: &[usize],
+    member_count: usize,
+    per_issue: usize,
+    rng: &mut impl Rng,
+) -> Vec<Vec<usize>> {
+    let mut counts = vec![0; member_count];
+    let mut assignements: Vec<Vec<usize>> = vec![Vec::new(); issue_order.len()];
+    for &i in issue_order {
+        let mut candidates: Vec<usize> = (0..member_count).collect();
+        candidates.shuffle(rng);
+        // stable, so the shuffled order is kept between members with the same count
+        candidates.sort_by_key(|&member| counts[member]);
+        for &member in candidates.iter().take(per_issue) {
+            counts[member] += 1;
+            assignements[i].push(member);
+        }
+    }
+    assignements
+}
+
+/// Moves members away from the issues they are already assigned to in `current`, swapping with
+/// other issues first so the balance is kept. Otherwise the issue goes to another member only if
+/// it stays within their fair share and `limits`.
+///
+/// Returns the number of issues where it was not possible.
+pub fn avoid_current_assignees(
+    assignements: &mut [Vec<usize>],
+    current: &[Vec<usize>],
+    member_count: usize,
+    limits: MemberLimits,
+    rng: &mut impl Rng,
+) -> usize {
+    let mut counts = vec![0; member_count];
+    for &member in assignements.iter().flatten() {
+        counts[member] += 1;
+    }
+    let fair_share = counts.iter().sum::<usize>().div_ceil(member_count.max(1));
+    let max = limits.max.map_or(fair_share, |max| max.min(fair_share));
+
+    let mut stuck = 0;
+    for i in 0..assignements.len() {
+        for slot in 0..assignements[i].len() {
+            let member = assignements[i][slot];
+            if !current[i].contains(&member) {
+                continue;
+            }
+
+            let mut swap = None;
+            'search: for j in 0..assignements.len() {
+                if j == i || assignements[j].contains(&member) || current[j].contains(&member) {
+                    continue;
+                }
+                for (other_slot, &other) in assignements[j].iter().enumerate() {
+                    if !current[i].contains(&other) && !assignements[i].contains(&other) {
+                        swap = Some((j, other_slot));
+                        break 'search;
+                    }
+                }
+            }
+
+            if let Some((j, other_slot)) = swap {
+                assignements[i][slot] = assignements[j][other_slot];
+                assignements[j][other_slot] = member;
+                continue;
+            }
+
+            if counts[member] <= limits.min {
+                stuck += 1;
+                continue;
+            }
+            let candidates: Vec<usize> = (0..member_count)
+                .filter(|&m| {
+                    !current[i].contains(&m) && !assignements[i].contains(&m) && counts[m] < max
+                })
+                .collect();
+            match candidates.choose(rng) {
+                Some(&candidate) => {
+                    assignements[i][slot] = candidate;
+                    counts[member] -= 1;
+                    counts[candidate] += 1;
+                }
+                None => stuck += 1,
+            }
+        }
+    }
+    stuck
+}
+
+/// Issue `i` goes to member `i`, wrapping around the members.
+pub fn distribute_round_robin(
+    issue_count: usize,
+    member_count: usize,
+    per_issue: usize,
+) -> Vec<Vec<usize>> {
+    (0..issue_count)
+        .map(|i| {
+            (0..per_issue)
+                .map(|k| (i * per_issue + k) % member_count)
+                .collect()
+        })
+        .collect()
+}
+
+/// Balances the total weight each member gets: the heaviest issues are handed out first, each to
+/// the least loaded members.
+pub fn distribute_by_weight(
+    weights: &[i32],
+    member_count: usize,
+    per_issue: usize,
+    limits: MemberLimits,
+    rng: &mut impl Rng,
+) -> Vec<Vec<usize>> {
+    distribute_greedy(weights, member_count, per_issue, limits, false, rng)
+}
+
+/// Same as [`distribute_by_weight`], but each issue goes to the members whose highest share of the
+/// issues or of the total weight stays the lowest once they get it, so both end up close to even.
+pub fn distribute_by_count_and_weight(
+    weights: &[i32],
+    member_count: usize,
+    per_issue: usize,
+    limits: MemberLimits,
+    rng: &mut impl Rng,
+) -> Vec<Vec<usize>> {
+    distribute_greedy(weights, member_count, per_issue, limits, true, rng)
+}
+
+fn distribute_greedy(
+    weights: &[i32],
+    member_count: usize,
+    per_issue: usize,
+    limits: MemberLimits,
+    by_count: bool,
+    rng: &mut impl Rng,
+) -> Vec<Vec<usize>> {
+    // the shares are compared without dividing: count / total_slots against load / total_weight
+    let total_slots = (weights.len() * per_issue) as i64;
+    let total_weight = (weights.iter().map(|&w| w as i64).sum::<i64>() * per_issue as i64).max(1);
+
+    let mut issue_order: Vec<usize> = (0..weights.len()).collect();
+    issue_order.sort_by_key(|&i| std::cmp::Reverse(weights[i]));
+
+    // shuffled so that ties between equally loaded members are broken randomly
+    let mut members: Vec<usize> = (0..member_count).collect();
+    members.shuffle(rng);
+
+    let mut loads = vec![0; member_count];
+    let mut counts = vec![0; member_count];
+    let mut assignements: Vec<Vec<usize>> = vec![Vec::new(); weights.len()];
+    for (handed_out, i) in issue_order.into_iter().enumerate() {
+        // once the remaining slots are only enough to reach the minimums, they go to the members below it
+        let remaining = (weights.len() - handed_out) * per_issue;
+        let missing: usize = counts
+            .iter()
+            .map(|&count| limits.min.saturating_sub(count))
+            .sum();
+        let mut candidates: Vec<usize> = members
+            .iter()
+            .copied()
+            .filter(|&m| limits.max.is_none_or(|max| counts[m] < max))
+            .filter(|&m| missing < remaining || counts[m] < limits.min)
+            .collect();
+        if candidates.len() < per_issue {
+            candidates = members.clone();
+        }
+
+        if by_count {
+            candidates.sort_by_key(|&m| {
+                let count_share = (counts[m] + 1) as i64 * total_weight;
+                let weight_share = (loads[m] + weights[i]) as i64 * total_slots;
+                (count_share.max(weight_share), count_share + weight_share)
+            });
+        } else {
+            candidates.sort_by_key(|&m| loads[m]);
+        }
+        for &member in candidates.iter().take(per_issue) {
+            loads[member] += weights[i];
+            counts[member] += 1;
+            assignements[i].push(member);
+        }
+    }
+    assignements
+}
+
+/// Deals the issues from the oldest to the newest to the members with the fewest issues so far, so
+/// that the oldest issues are spread between the members.
+pub fn distribute_by_age(
+    created_at: &[i64],
+    member_count: usize,
+    per_issue: usize,
+    rng: &mut impl Rng,
+) -> Vec<Vec<usize>> {
+    let mut issue_order: Vec<usize> = (0..created_at.len()).collect();
+    issue_order.sort_by_key(|&i| created_at[i]);
+    deal_least_loaded(&issue_order, member_count, per_issue, rng)
+}
+
+/// Hands the issues of the `removed` member to the least loaded of the others.
+///
+/// The indices above the removed one are shifted down so they match the member list without it.
+pub fn remove_member(
+    assignements: &mut [Vec<usize>],
+    removed: usize,
+    member_count: usize,
+    weights: &[i32],
+    rng: &mut impl Rng,
+) {
+    let mut loads = vec![0; member_count];
+    for (i, assignees) in assignements.iter().enumerate() {
+        for &member in assignees {
+            loads[member] += weights[i];
+        }
+    }
+
+    // shuffled so that ties between equally loaded members are broken randomly
+    let mut members: Vec<usize> = (0..member_count).filter(|&m| m != removed).collect();
+    members.shuffle(rng);
+
+    for (i, assignees) in assignements.iter_mut().enumerate() {
+        for slot in 0..assignees.len() {
+            if assignees[slot] != removed {
+                continue;
+            }
+            let candidate = members
+                .iter()
+                .copied()
+                .filter(|m| !assignees.contains(m))
+                .min_by_key(|&m| loads[m]);
+            if let Some(candidate) = candidate {
+                loads[candidate] += weights[i];
+                assignees[slot] = candidate;
+            }
+        }
+        assignees.retain(|&m| m != removed);
+        for member in assignees.iter_mut() {
+            if *member > removed {
+                *member -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn counts(assignements: &[Vec<usize>], member_count: usize) -> Vec<usize> {
+        let mut counts = vec![0; member_count];
+        for assignees in assignements {
+            for &member in assignees {
+                counts[member] += 1;
+            }
+        }
+        counts
+    }
+
+    // every assignee is a valid member, and appears at most once on each issue
+    fn assert_valid(
+        assignements: &[Vec<usize>],
+        issue_count: usize,
+        member_count: usize,
+        per_issue: usize,
+    ) {
+        assert_eq!(assignements.len(), issue_count);
+        for assignees in assignements {
+            assert_eq!(assignees.len(), per_issue);
+            assert!(assignees.iter().all(|&member| member < member_count));
+            let mut distinct = assignees.clone();
+            distinct.sort_unstable();
+            distinct.dedup();
+            assert_eq!(distinct.len(), per_issue);
+        }
+    }
+
+    const NO_LIMITS: MemberLimits = MemberLimits { min: 0, max: None };
+
+    #[test]
+    fn distributions_are_valid() {
+        for issue_count in 0..10 {
+            for member_count in 1..5 {
+                for per_issue in 1..=member_count {
+                    for seed in 0..10 {
+                        let mut rng = StdRng::seed_from_u64(seed);
+                        let weights: Vec<i32> = (0..issue_count as i32).map(|i| i % 4).collect();
+                        let created_at: Vec<i64> = (0..issue_count as i64).rev().collect();
+                        for assignements in [
+                            distribute(issue_count, member_count, per_issue, &mut rng),
+                            distribute_round_robin(issue_count, member_count, per_issue),
+                            distribute_by_weight(
+                                &weights,
+                                member_count,
+                                per_issue,
+                                NO_LIMITS,
+                                &mut rng,
+                            ),
+                            distribute_by_count_and_weight(
+                                &weights,
+                                member_count,
+                                per_issue,
+                                NO_LIMITS,
+                                &mut rng,
+                            ),
+                            distribute_by_age(&created_at, member_count, per_issue, &mut rng),
+                        ] {
+                            assert_valid(&assignements, issue_count, member_count, per_issue);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn remove_member_keeps_every_slot() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut assignements = distribute(9, 4, 2, &mut rng);
+            remove_member(&mut assignements, 1, 4, &[1; 9], &mut rng);
+            assert_valid(&assignements, 9, 3, 2);
+            let counts = counts(&assignements, 3);
+            assert_eq!(counts.iter().sum::<usize>(), 18);
+        }
+    }
+
+    #[test]
+    fn check_rejects_impossible_limits() {
+        let limits = MemberLimits {
+            min: 0,
+            max: Some(2),
+        };
+        assert!(limits.check(6, 3, 1).is_ok());
+        assert!(limits.check(7, 3, 1).is_err());
+        let limits = MemberLimits { min: 3, max: None };
+        assert!(limits.check(6, 3, 1).is_err());
+        assert!(limits.check(9, 3, 1).is_ok());
+    }
+
+    #[test]
+    fn distribute_counts_differ_by_at_most_one() {
+        for issue_count in 0..12 {
+            for member_count in 1..6 {
+                for per_issue in 1..=member_count {
+                    for seed in 0..20 {
+                        let mut rng = StdRng::seed_from_u64(seed);
+                        let assignements =
+                            distribute(issue_count, member_count, per_issue, &mut rng);
+                        let counts = counts(&assignements, member_count);
+                        let max = counts.iter().max().unwrap();
+                        let min = counts.iter().min().unwrap();
+                        let fair_share = issue_count * per_issue / member_count;
+                        assert!(
+                            *min == fair_share && max - min <= 1,
+                            "{} issues, {} members, {} per issue, seed {}: {:?}",
+                            issue_count,
+                            member_count,
+                            per_issue,
+                            seed,
+                            counts
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multiple_assignees_are_not_always_paired_together() {
+        // a roll can still split the members the same way every time by chance, but not most rolls
+        let created_at: Vec<i64> = (0..8).collect();
+        let mut varied = [0; 2];
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let rolls = [
+                distribute(8, 4, 2, &mut rng),
+                distribute_by_age(&created_at, 4, 2, &mut rng),
+            ];
+            for (k, assignements) in rolls.into_iter().enumerate() {
+                let mut pairs: Vec<Vec<usize>> = assignements
+                    .into_iter()
+                    .map(|mut pair| {
+                        pair.sort_unstable();
+                        pair
+                    })
+                    .collect();
+                pairs.sort_unstable();
+                pairs.dedup();
+                if pairs.len() > 2 {
+                    varied[k] += 1;
+                }
+            }
+        }
+        assert!(varied.iter().all(|&count| count > 40), "{:?}", varied);
+    }
+
+    #[test]
+    fn avoiding_current_assignees_keeps_the_share_and_limits() {
+        let limits = MemberLimits {
+            min: 0,
+            max: Some(1),
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut assignements = vec![vec![0], vec![1]];
+        let stuck =
+            avoid_current_assignees(&mut assignements, &[vec![0], vec![0]], 2, limits, &mut rng);
+        assert_eq!(stuck, 1);
+        assert_eq!(counts(&assignements, 2), [1, 1]);
+
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut assignements = distribute(6, 3, 1, &mut rng);
+            let current = vec![vec![0]; 6];
+            let stuck =
+                avoid_current_assignees(&mut assignements, &current, 3, NO_LIMITS, &mut rng);
+            let counts = counts(&assignements, 3);
+            assert!(counts.iter().all(|&count| count <= 2), "{:?}", counts);
+            // the other members already have their share, so member 0 keeps its two issues
+            assert_eq!(stuck, 2);
+        }
+    }
+}
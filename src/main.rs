@@ -1,39 +1,236 @@
-use clap::Parser;
+use ansi_escapes::{CursorHide, CursorShow, CursorUp, EraseLine};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use config::{self, Config, ConfigError, File, FileFormat};
-use dialoguer::Confirm;
-use dialoguer::{theme::ColorfulTheme, Input, MultiSelect, Select};
-use rand::seq::SliceRandom;
-use rand::{self, Rng};
-use reqwest::blocking::Client;
+use dialoguer::theme::{ColorfulTheme, SimpleTheme, Theme};
+use dialoguer::{Confirm, FuzzySelect, Input, MultiSelect, Select};
+use env_logger::WriteStyle;
+use gitlab_roulette::{
+    avoid_current_assignees, distribute, distribute_by_age, distribute_by_count_and_weight,
+    distribute_by_weight, distribute_round_robin, remove_member, MemberLimits,
+};
+use log::{debug, info, warn, LevelFilter};
+use rand::{self, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Certificate, NoProxy, Proxy, StatusCode};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::{fmt::Display, fs, process};
-use url::Url;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fmt::Display, fs, process, thread};
+use url::{form_urlencoded, Url};
 
 #[derive(Parser, Debug)]
-#[command(name = "gitlab roulette")]
+#[command(
+    name = "gitlab roulette",
+    version = VERSION,
+    args_conflicts_with_subcommands = true,
+    after_help = "Exit codes:\n  0  success, or nothing to assign\n  1  error\n  2  invalid config, or authentication failed\n  3  network error\n  4  cancelled\n  5  some of the issues could not be assigned"
+)]
 struct Cli {
-    #[arg(id = "url", short, long, help = "URL of the project")]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // running without a subcommand is the same as running roll
+    #[command(flatten)]
+    roll: RollArgs,
+
+    #[arg(id = "url", short, long, global = true, help = "URL of the project")]
     url: Option<String>,
 
-    #[arg(id = "token", short, long, help = "Gitlab token to use to connect")]
+    #[arg(
+        id = "group",
+        short,
+        long,
+        global = true,
+        help = "Path of a group to take the issues and members from instead of a single project (e.g. my-org/my-team)"
+    )]
+    group: Option<String>,
+
+    #[arg(
+        id = "project_id",
+        long = "project-id",
+        global = true,
+        help = "Numeric id of the project, skips looking for the project using the url (which is then only used to find the gitlab instance)"
+    )]
+    project_id: Option<i64>,
+
+    #[arg(
+        id = "token",
+        short,
+        long,
+        global = true,
+        help = "Gitlab token to use to connect (takes precedence over --token-file, the GITLAB_TOKEN environment variable and the config file, in that order)"
+    )]
     token: Option<String>,
 
+    #[arg(
+        id = "token_file",
+        long = "token-file",
+        global = true,
+        help = "File containing the Gitlab token to use to connect"
+    )]
+    token_file: Option<String>,
+
+    #[arg(
+        id = "auth_type",
+        long = "auth-type",
+        global = true,
+        help = "Kind of token given, oauth tokens are sent as a bearer token [default: private]"
+    )]
+    auth_type: Option<AuthType>,
+
     #[arg(
         id = "config_file",
-        long,
+        long = "config-file",
+        alias = "config_file",
+        global = true,
         help = "File to use as config",
         default_value = "./gitlab-roulette.toml"
     )]
-    config_file: Option<String>,
+    config_file: String,
+
+    #[arg(
+        id = "config_format",
+        long = "config-format",
+        global = true,
+        help = "Format of the config file (guessed from the file extension if not specified, defaults to toml)"
+    )]
+    config_format: Option<ConfigFormat>,
+
+    #[arg(
+        id = "timeout",
+        long,
+        global = true,
+        help = "Timeout of the requests made to Gitlab, in seconds",
+        default_value_t = 30
+    )]
+    timeout: u64,
+
+    #[arg(
+        id = "proxy",
+        long,
+        global = true,
+        help = "Proxy to use to reach Gitlab (the HTTPS_PROXY, HTTP_PROXY and NO_PROXY environment variables are used otherwise)"
+    )]
+    proxy: Option<String>,
+
+    #[arg(
+        id = "insecure",
+        long,
+        global = true,
+        help = "Accept invalid TLS certificates (e.g. self-signed ones)"
+    )]
+    insecure: bool,
+
+    #[arg(
+        id = "ca_cert",
+        long = "ca-cert",
+        global = true,
+        help = "PEM file of an additional root certificate to trust"
+    )]
+    ca_cert: Option<String>,
+
+    #[arg(
+        id = "cache_ttl",
+        long = "cache-ttl",
+        global = true,
+        help = "How long the projects list is cached, in seconds (defaults to one hour)"
+    )]
+    cache_ttl: Option<u64>,
+
+    #[arg(
+        id = "no_cache",
+        long = "no-cache",
+        global = true,
+        help = "Do not use nor update the projects cache"
+    )]
+    no_cache: bool,
+
+    #[arg(
+        id = "refresh",
+        long,
+        global = true,
+        help = "Fetch the projects list again even if the cache is still fresh"
+    )]
+    refresh: bool,
+
+    #[arg(
+        id = "history_file",
+        long = "history-file",
+        global = true,
+        help = "File in which the previous assignees are saved after each roll, used by the undo command",
+        default_value = "./.gitlab-roulette-history.json"
+    )]
+    history_file: String,
+
+    #[arg(
+        id = "verbose",
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        help = "Log the requests made to Gitlab to stderr (-v for urls and status codes, -vv to also log the response bodies)"
+    )]
+    verbose: u8,
+
+    #[arg(
+        id = "no_color",
+        long = "no-color",
+        global = true,
+        help = "Disable colors in the prompts (also disabled when the NO_COLOR environment variable is set)"
+    )]
+    no_color: bool,
+
+    #[arg(
+        id = "legacy_assign",
+        long = "legacy-assign",
+        global = true,
+        help = "Assign the issues using the assignee_id parameter of old gitlab versions, which only supports a single assignee (detected automatically before gitlab 11)"
+    )]
+    legacy_assign: bool,
+
+    #[arg(
+        id = "error_format",
+        long = "error-format",
+        global = true,
+        help = "Format of the error printed to stderr when failing, json prints a single line object with kind, message, status_code and url",
+        default_value_t = ErrorFormat::Text
+    )]
+    error_format: ErrorFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "Assign the issues randomly to the members (default)")]
+    Roll(Box<RollArgs>),
+    #[command(about = "Put back the assignees the issues had before the last roll")]
+    Undo,
+    #[command(about = "Show the user the token belongs to")]
+    Whoami,
+    #[command(
+        about = "List the members of the project with the number of open issues assigned to them"
+    )]
+    ListMembers,
+}
 
+#[derive(Args, Debug)]
+struct RollArgs {
     #[arg(
         id = "issue",
         short,
         long,
-        help = "The id of the issue you want to assign (can be used multiple times to assign multiple issues) (you will be prompted if this isn't specified)"
+        visible_alias = "issues",
+        value_delimiter = ',',
+        help = "The number (#) of the issue you want to assign (can be used multiple times or separated by commas to assign multiple issues, - reads them from stdin) (you will be prompted if this isn't specified)"
     )]
-    issues: Option<Vec<i32>>,
+    issues: Option<Vec<String>>,
 
     #[arg(
         id = "member",
@@ -42,52 +239,771 @@ struct Cli {
         help = "The username of the member you want to assign the issues to (can be used multiple times to specify multiple members) (you will be prompted if this isn't specified)"
     )]
     members: Option<Vec<String>>,
-}
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GitlabProject {
-    id: i32,
-    name: String,
-    path_with_namespace: String,
-    web_url: String,
+    #[arg(
+        id = "project",
+        long,
+        help = "The URL of another project whose issues are assigned in the same roll (can be used multiple times) (only the members of every project are used)"
+    )]
+    projects: Option<Vec<String>>,
+
+    #[arg(
+        id = "member_file",
+        long = "member-file",
+        help = "File with one username or user id per line, added to the members even if they are not part of the project"
+    )]
+    member_file: Option<String>,
+
+    #[arg(
+        id = "exclude",
+        long,
+        help = "The username of a member that should never be assigned issues (can be used multiple times to exclude multiple members)"
+    )]
+    exclude: Option<Vec<String>>,
+
+    #[arg(
+        id = "milestone",
+        long,
+        help = "The exact title of a milestone whose issues you want to assign (can be used multiple times to use multiple milestones) (skips the issue selection prompts)"
+    )]
+    milestones: Option<Vec<String>>,
+
+    #[arg(
+        id = "milestone_contains",
+        long = "milestone-contains",
+        help = "Same as --milestone, but selects every milestone whose title contains the given text"
+    )]
+    milestones_contains: Option<Vec<String>>,
+
+    #[arg(
+        id = "only_milestone_current",
+        long = "only-milestone-current",
+        help = "Only use the issues of the milestone that started before today and is due after it (falls back to the milestone prompt when there is none or several)",
+        conflicts_with_all = ["milestone", "milestone_contains", "iteration", "search"]
+    )]
+    only_milestone_current: bool,
+
+    #[arg(
+        id = "iteration",
+        long,
+        help = "Only use the issues of this iteration, either its id or current (skips the issue selection prompts)",
+        conflicts_with_all = ["milestone", "milestone_contains"]
+    )]
+    iteration: Option<String>,
+
+    #[arg(
+        id = "search",
+        long,
+        help = "Only use the issues matching this term in their title or description, searched by GitLab (skips the issue selection prompts)",
+        conflicts_with_all = ["milestone", "milestone_contains", "iteration"]
+    )]
+    search: Option<String>,
+
+    #[arg(
+        id = "include_closed_milestones",
+        long = "include-closed-milestones",
+        help = "Also list the closed milestones in the milestone picker"
+    )]
+    include_closed_milestones: bool,
+
+    #[arg(
+        id = "webhook",
+        long,
+        help = "URL the assignments are POSTed to as JSON (same format as --output-format json) after the roll"
+    )]
+    webhook: Option<String>,
+
+    #[arg(
+        id = "seed",
+        long,
+        help = "Seed of the random draw, the same seed with the same issues and members always gives the same assignments"
+    )]
+    seed: Option<u64>,
+
+    #[arg(
+        id = "count",
+        long,
+        help = "Only assign this many issues, picked randomly among the selected ones"
+    )]
+    count: Option<usize>,
+
+    #[arg(
+        id = "limit_members",
+        long = "limit-members",
+        help = "Only give issues to this many members, picked randomly among the selected ones"
+    )]
+    limit_members: Option<usize>,
+
+    #[arg(
+        id = "assignees_per_issue",
+        long = "assignees-per-issue",
+        help = "The number of distinct members to assign to each issue",
+        default_value_t = 1
+    )]
+    assignees_per_issue: usize,
+
+    #[arg(
+        id = "mode",
+        long,
+        help = "How the issues are distributed between the members [default: random]",
+        conflicts_with = "by_weight"
+    )]
+    mode: Option<AssignMode>,
+
+    #[arg(
+        id = "spread_by_age",
+        long = "spread-by-age",
+        help = "Hand out the issues from the oldest to the newest in turn, so nobody gets all the oldest ones",
+        conflicts_with_all = ["mode", "by_weight"]
+    )]
+    spread_by_age: bool,
+
+    #[arg(
+        id = "by_weight",
+        long = "by-weight",
+        help = "Balance the total weight of the issues each member gets instead of their number (issues without a weight count as 1)"
+    )]
+    by_weight: bool,
+
+    #[arg(
+        id = "balance_by",
+        long = "balance-by",
+        help = "What to keep even between the members: the number of issues, their total weight (same as --by-weight) or both at once",
+        conflicts_with_all = ["mode", "by_weight", "spread_by_age"]
+    )]
+    balance_by: Option<BalanceBy>,
+
+    #[arg(
+        id = "by_priority",
+        long = "by-priority",
+        help = "Balance the total priority of the issues each member gets, using the multipliers of the priorities table of the config (issues without a listed label count as 1)",
+        conflicts_with_all = ["mode", "by_weight", "spread_by_age"]
+    )]
+    by_priority: bool,
+
+    #[arg(
+        id = "min_per_member",
+        long = "min-per-member",
+        help = "Minimum number of issues each member gets"
+    )]
+    min_per_member: Option<usize>,
+
+    #[arg(
+        id = "max_per_member",
+        long = "max-per-member",
+        help = "Maximum number of issues a member can get"
+    )]
+    max_per_member: Option<usize>,
+
+    #[arg(
+        id = "avoid_current",
+        long = "avoid-current",
+        help = "Never give an issue back to one of the members it is currently assigned to, when possible"
+    )]
+    avoid_current: bool,
+
+    #[arg(
+        id = "interactive_confirm",
+        long = "interactive-confirm",
+        help = "Confirm, change or skip each assignment one by one instead of confirming them all at once"
+    )]
+    interactive_confirm: bool,
+
+    #[arg(
+        id = "yes",
+        short,
+        long,
+        help = "Accept the plan without asking and use every issue and member that are not given through the config or args. The assignment itself is still confirmed, unless --commit or --force is given, and fails when nothing can be asked",
+        conflicts_with = "interactive_confirm"
+    )]
+    yes: bool,
+
+    #[arg(
+        id = "commit",
+        long,
+        help = "Assign the issues without the final confirmation once the plan is accepted with --yes",
+        requires = "yes"
+    )]
+    commit: bool,
+
+    #[arg(
+        id = "force",
+        long,
+        help = "Skip every confirmation, same as --yes --commit",
+        conflicts_with = "interactive_confirm"
+    )]
+    force: bool,
+
+    #[arg(
+        id = "no_animation",
+        long = "no-animation",
+        help = "Reveal the assignments instantly instead of spinning through the members"
+    )]
+    no_animation: bool,
+
+    #[arg(
+        id = "check_write",
+        long = "check-write",
+        help = "Check that the token can assign issues before selecting anything, by giving an issue its current assignees back (only the token scopes are checked with --dry-run)"
+    )]
+    check_write: bool,
+
+    #[arg(
+        id = "format",
+        long,
+        help = "Format of the preview of the assignments, table prints one line per issue without the animation",
+        default_value_t = PreviewFormat::Plain
+    )]
+    format: PreviewFormat,
+
+    #[arg(
+        id = "summary_format",
+        long = "summary-format",
+        help = "Format of the summary printed once the issues are assigned, markdown also lists them with links and mentions to paste in a chat",
+        default_value_t = SummaryFormat::Text
+    )]
+    summary_format: SummaryFormat,
+
+    #[arg(
+        id = "dry_run",
+        long = "dry-run",
+        help = "Show the planned assignments and exit without assigning anything"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        id = "output_format",
+        long = "output-format",
+        help = "Format of the dry run output, json prints the plan to stdout without asking anything (every issue and member is used unless specified)",
+        default_value_t = OutputFormat::Text,
+        requires = "dry_run"
+    )]
+    output_format: OutputFormat,
+
+    #[arg(
+        id = "suspense",
+        long,
+        help = "Pause before revealing each issue, for rolls done live in front of the team"
+    )]
+    suspense: bool,
+
+    #[arg(
+        id = "reveal_delay",
+        long = "reveal-delay",
+        help = "Length of the pause before each issue with --suspense, in milliseconds",
+        default_value_t = 1500,
+        requires = "suspense"
+    )]
+    reveal_delay: u64,
+
+    #[arg(
+        id = "comment",
+        long,
+        help = "Post a comment on each assigned issue (the text can be changed using comment_template in the config file)"
+    )]
+    comment: bool,
+
+    #[arg(
+        id = "concurrency",
+        long,
+        help = "Maximum number of issues assigned at the same time",
+        default_value_t = 8
+    )]
+    concurrency: usize,
+
+    #[arg(
+        id = "profile",
+        long,
+        help = "Name of a profile of the config file to take the members, issue selection, filters and balancing from (the args still take precedence)"
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        id = "save_profile",
+        long = "save-profile",
+        help = "Save the selected members, issue selection, filters and balancing in the config file under this profile name"
+    )]
+    save_profile: Option<String>,
+
+    #[arg(
+        id = "type",
+        long,
+        help = "Only use the issues of this type (can be used multiple times to use multiple types) (all types are used if this isn't specified)"
+    )]
+    types: Option<Vec<IssueType>>,
+
+    #[arg(
+        id = "sort",
+        long,
+        help = "Order in which the issues are listed",
+        default_value_t = IssueSort::Iid
+    )]
+    sort: IssueSort,
+
+    #[arg(
+        id = "state",
+        long,
+        help = "Only use the issues in this state [default: opened]"
+    )]
+    state: Option<IssueState>,
+
+    #[arg(
+        id = "unassigned_only",
+        long = "unassigned-only",
+        help = "Only use the issues nobody is assigned to"
+    )]
+    unassigned_only: bool,
+
+    #[arg(
+        id = "include_confidential",
+        long = "include-confidential",
+        help = "Also assign the confidential issues, which are skipped by default"
+    )]
+    include_confidential: bool,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GitlabIssue {
-    id: i32,
-    iid: i32,
-    project_id: i32,
-    title: String,
-    description: String,
-    state: String,
-    r#type: String,
-    assignees: Vec<GitlabProjectMember>,
-    milestone: Option<GitlabMilestone>,
-    labels: Vec<String>,
+impl RollArgs {
+    // the args replace the whole issue selection of a profile, not only the keys they set
+    fn selects_issues(&self) -> bool {
+        self.issues.is_some()
+            || self.milestones.is_some()
+            || self.milestones_contains.is_some()
+            || self.only_milestone_current
+            || self.iteration.is_some()
+            || self.search.is_some()
+    }
+
+    // same for the way the issues are balanced
+    fn balances_issues(&self) -> bool {
+        self.mode.is_some()
+            || self.spread_by_age
+            || self.by_weight
+            || self.balance_by.is_some()
+            || self.by_priority
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GitlabMilestone {
-    id: i32,
-    project_id: i32,
-    title: String,
-    description: String,
-    state: String,
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct GitlabProjectMember {
-    id: i32,
-    username: String,
-    name: String,
+impl ConfigFormat {
+    fn from_path(path: &str) -> ConfigFormat {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        match extension.as_deref() {
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            Some("json") => ConfigFormat::Json,
+            _ => ConfigFormat::Toml,
+        }
+    }
 }
 
-#[derive(Debug)]
+impl From<ConfigFormat> for FileFormat {
+    fn from(format: ConfigFormat) -> Self {
+        match format {
+            ConfigFormat::Toml => FileFormat::Toml,
+            ConfigFormat::Yaml => FileFormat::Yaml,
+            ConfigFormat::Json => FileFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AuthType {
+    Private,
+    Oauth,
+}
+
+impl Display for AuthType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthType::Private => write!(f, "private"),
+            AuthType::Oauth => write!(f, "oauth"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AssignMode {
+    Random,
+    RoundRobin,
+}
+
+impl Display for AssignMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssignMode::Random => write!(f, "random"),
+            AssignMode::RoundRobin => write!(f, "round-robin"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum BalanceBy {
+    Count,
+    Weight,
+    Both,
+}
+
+impl Display for BalanceBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BalanceBy::Count => write!(f, "count"),
+            BalanceBy::Weight => write!(f, "weight"),
+            BalanceBy::Both => write!(f, "both"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum IssueType {
+    Issue,
+    Incident,
+    Task,
+    #[value(name = "test_case")]
+    TestCase,
+}
+
+impl IssueType {
+    // gitlab returns the type in upper case (ISSUE, TEST_CASE...)
+    fn matches(&self, issue: &GitlabIssue) -> bool {
+        issue.r#type.eq_ignore_ascii_case(&self.to_string())
+    }
+}
+
+impl Display for IssueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IssueType::Issue => write!(f, "issue"),
+            IssueType::Incident => write!(f, "incident"),
+            IssueType::Task => write!(f, "task"),
+            IssueType::TestCase => write!(f, "test_case"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum IssueSort {
+    Iid,
+    Title,
+    Milestone,
+}
+
+impl IssueSort {
+    fn sort(&self, issues: &mut [GitlabIssue]) {
+        match self {
+            IssueSort::Iid => issues.sort_by_key(|issue| issue.iid),
+            IssueSort::Title => {
+                issues.sort_by(|a, b| a.title.cmp(&b.title).then(a.iid.cmp(&b.iid)))
+            }
+            // issues without a milestone go last
+            IssueSort::Milestone => issues.sort_by(|a, b| {
+                let a_milestone = a.milestone.as_ref().map(|milestone| &milestone.title);
+                let b_milestone = b.milestone.as_ref().map(|milestone| &milestone.title);
+                a_milestone
+                    .is_none()
+                    .cmp(&b_milestone.is_none())
+                    .then(a_milestone.cmp(&b_milestone))
+                    .then(a.iid.cmp(&b.iid))
+            }),
+        }
+    }
+}
+
+impl Display for IssueSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IssueSort::Iid => write!(f, "iid"),
+            IssueSort::Title => write!(f, "title"),
+            IssueSort::Milestone => write!(f, "milestone"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PreviewFormat {
+    Plain,
+    Table,
+}
+
+impl Display for PreviewFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreviewFormat::Plain => write!(f, "plain"),
+            PreviewFormat::Table => write!(f, "table"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SummaryFormat {
+    Text,
+    Markdown,
+}
+
+impl Display for SummaryFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SummaryFormat::Text => write!(f, "text"),
+            SummaryFormat::Markdown => write!(f, "markdown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+impl Display for ErrorFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorFormat::Text => write!(f, "text"),
+            ErrorFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum IssueState {
+    Opened,
+    Closed,
+    All,
+}
+
+impl Display for IssueState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IssueState::Opened => write!(f, "opened"),
+            IssueState::Closed => write!(f, "closed"),
+            IssueState::All => write!(f, "all"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct GitlabProject {
+    id: i32,
+    name: String,
+    path_with_namespace: String,
+    web_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct GitlabGroup {
+    id: i32,
+    name: String,
+    full_path: String,
+    web_url: String,
+}
+
+// where the issues and members are taken from
+#[derive(Debug)]
+enum Scope {
+    Project(GitlabProject),
+    Group(GitlabGroup),
+}
+
+impl Scope {
+    // path of the project or group in the api
+    fn path(&self) -> String {
+        match self {
+            Scope::Project(project) => format!("projects/{}", project.id),
+            Scope::Group(group) => format!("groups/{}", group.id),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PlannedRoll {
+    // not set when rolling over a group or several projects, see the project_id of each issue instead
+    project_id: Option<i32>,
+    issues: Vec<PlannedIssue>,
+}
+
+#[derive(Debug, Serialize)]
+struct PlannedIssue {
+    project_id: i32,
+    iid: i32,
+    title: String,
+    assignees: Vec<PlannedAssignee>,
+}
+
+#[derive(Debug, Serialize)]
+struct PlannedAssignee {
+    id: i32,
+    username: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GitlabIssue {
+    id: i32,
+    iid: i32,
+    project_id: i32,
+    title: String,
+    description: String,
+    state: String,
+    r#type: String,
+    assignees: Vec<GitlabProjectMember>,
+    milestone: Option<GitlabMilestone>,
+    labels: Vec<String>,
+    weight: Option<i32>,
+    created_at: DateTime<Utc>,
+    confidential: bool,
+    web_url: String,
+    iteration: Option<GitlabIteration>,
+}
+
+impl GitlabIssue {
+    // issues without a weight count as 1
+    fn weight_or_default(&self) -> i32 {
+        self.weight.unwrap_or(1)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GitlabMilestone {
+    id: i32,
+    // project milestones have a project_id, group milestones a group_id
+    project_id: Option<i32>,
+    group_id: Option<i32>,
+    title: String,
+    description: String,
+    state: String,
+    start_date: Option<NaiveDate>,
+    due_date: Option<NaiveDate>,
+}
+
+impl GitlabMilestone {
+    fn is_current(&self) -> bool {
+        let today = Local::now().date_naive();
+        self.start_date.is_some_and(|start| start <= today)
+            && self.due_date.is_some_and(|due| today <= due)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GitlabIteration {
+    id: i32,
+    // iterations of a cadence usually have no title
+    title: Option<String>,
+    start_date: Option<NaiveDate>,
+    due_date: Option<NaiveDate>,
+}
+
+impl GitlabIteration {
+    fn is_current(&self) -> bool {
+        let today = Local::now().date_naive();
+        self.start_date.is_some_and(|start| start <= today)
+            && self.due_date.is_some_and(|due| today <= due)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GitlabUser {
+    id: i32,
+    username: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GitlabVersion {
+    version: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GitlabAccessToken {
+    scopes: Vec<String>,
+    expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct GitlabProjectMember {
+    id: i32,
+    username: String,
+    name: String,
+}
+
+// selections saved under [profiles.<name>] in the config file
+#[derive(Debug, Default, Serialize)]
+struct Profile {
+    members: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selection: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    milestones: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    milestones_contains: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iteration: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    search: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unassigned_only: Option<bool>,
+    mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    balance_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spread_by_age: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    by_priority: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    types: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedProjects {
+    timestamp: u64,
+    projects: Vec<GitlabProject>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct HistoryEntry {
+    timestamp: u64,
+    issues: Vec<HistoryIssue>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct HistoryIssue {
+    project_id: i32,
+    iid: i32,
+    previous_assignee_ids: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
 enum IssueSelectionType {
     Milestone,
+    Iteration,
     Label,
     Range,
     Manual,
+    Search,
 }
 
 impl Display for IssueSelectionType {
@@ -96,9 +1012,56 @@ impl Display for IssueSelectionType {
     }
 }
 
+#[derive(Debug)]
+enum PreviewAction {
+    Confirm,
+    Reroll,
+    RemoveMember,
+    Cancel,
+}
+
+impl Display for PreviewAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreviewAction::Confirm => write!(f, "Confirm"),
+            PreviewAction::Reroll => write!(f, "Re-roll"),
+            PreviewAction::RemoveMember => {
+                write!(f, "Remove a member and give their issues to the others")
+            }
+            PreviewAction::Cancel => write!(f, "Cancel"),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PairingAction {
+    Accept,
+    Reassign,
+    Skip,
+}
+
+impl Display for PairingAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PairingAction::Accept => write!(f, "Accept"),
+            PairingAction::Reassign => write!(f, "Reassign to someone else"),
+            PairingAction::Skip => write!(f, "Skip this issue"),
+        }
+    }
+}
+
 impl Display for GitlabIssue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "#{}: {}", self.iid, self.title)
+        write!(f, "#{}: {}", self.iid, self.title)?;
+        if !self.assignees.is_empty() {
+            let usernames: Vec<&str> = self
+                .assignees
+                .iter()
+                .map(|member| member.username.as_str())
+                .collect();
+            write!(f, " [{}]", usernames.join(", "))?;
+        }
+        Ok(())
     }
 }
 
@@ -108,355 +1071,3250 @@ impl Display for GitlabProjectMember {
     }
 }
 
-impl Display for GitlabMilestone {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "%{}: {}", self.id, self.title)
-    }
+impl Display for GitlabMilestone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "%{}: {}", self.id, self.title)
+    }
+}
+
+impl Display for GitlabIteration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "*{}: ", self.id)?;
+        if let Some(title) = &self.title {
+            write!(f, "{} ", title)?;
+        }
+        match (self.start_date, self.due_date) {
+            (Some(start), Some(due)) => write!(f, "({} - {})", start, due),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl PartialEq for GitlabIteration {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl PartialEq for GitlabMilestone {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.project_id == other.project_id
+            && self.group_id == other.group_id
+    }
+}
+
+const BODY_LOG_LENGTH: usize = 500;
+// oldest version with every endpoint used here (token scopes, iterations)
+const MIN_GITLAB_VERSION: (u32, u32) = (15, 5);
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (commit ",
+    env!("GITLAB_ROULETTE_COMMIT"),
+    ", GitLab API v4)"
+);
+
+// exit codes, see the help
+const EXIT_ERROR: i32 = 1;
+const EXIT_CONFIG: i32 = 2;
+const EXIT_NETWORK: i32 = 3;
+const EXIT_CANCELLED: i32 = 4;
+const EXIT_PARTIAL_FAILURE: i32 = 5;
+
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Serialize)]
+struct ErrorReport {
+    kind: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+fn fail(code: i32, message: String) -> ! {
+    let kind = match code {
+        EXIT_CONFIG => "config",
+        EXIT_NETWORK => "network",
+        EXIT_CANCELLED => "cancelled",
+        EXIT_PARTIAL_FAILURE => "partial_failure",
+        _ => "error",
+    };
+    report_error(
+        code,
+        ErrorReport {
+            kind,
+            message,
+            status_code: None,
+            url: None,
+        },
+    )
+}
+
+fn report_error(code: i32, report: ErrorReport) -> ! {
+    if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!(
+            "{}",
+            serde_json::to_string(&report).expect("failed to serialize the error")
+        );
+    } else {
+        eprintln!("{}", report.message);
+    }
+    process::exit(code);
+}
+
+// informational messages go to stderr when stdout is reserved for machine readable output
+static MACHINE_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if MACHINE_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+const DEFAULT_CACHE_TTL_SECS: u64 = 60 * 60;
+
+static NO_COLOR: AtomicBool = AtomicBool::new(false);
+
+// theme used by every prompt
+fn theme() -> Box<dyn Theme> {
+    if NO_COLOR.load(Ordering::Relaxed) {
+        Box::new(SimpleTheme)
+    } else {
+        Box::new(ColorfulTheme::default())
+    }
+}
+
+// issues with this label are never assigned
+const DEFAULT_EXCLUDE_LABEL: &str = "no-roulette";
+
+const DEFAULT_COMMENT_TEMPLATE: &str = "Assigned via gitlab-roulette 🎲 to @{username}";
+
+const MAX_RATE_LIMITED_RETRIES: u32 = 5;
+
+const MAX_TRANSIENT_RETRIES: u32 = 3;
+
+// timeout of the http client, only kept around to report it when a request times out
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+
+// set by the ctrl-c handler during the assignments
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// set when the issues have to be assigned one member at a time, before gitlab 11.0 or with --legacy-assign
+static LEGACY_ASSIGN: AtomicBool = AtomicBool::new(false);
+
+// proxy used by the http client, only kept around to report proxy errors
+static PROXY: OnceLock<Option<String>> = OnceLock::new();
+
+// (major, minor) of the gitlab instance, None when it does not tell its version
+static GITLAB_VERSION: OnceLock<Option<(u32, u32)>> = OnceLock::new();
+
+// how the token is sent, personal access tokens and oauth tokens use different headers
+static AUTH_TYPE: OnceLock<AuthType> = OnceLock::new();
+
+fn add_auth(request: RequestBuilder, token: &str) -> RequestBuilder {
+    match AUTH_TYPE.get().unwrap_or(&AuthType::Private) {
+        AuthType::Private => request.header("PRIVATE-TOKEN", token),
+        AuthType::Oauth => request.bearer_auth(token),
+    }
+}
+
+fn try_send(request: RequestBuilder) -> Result<Response, String> {
+    let (client, request) = request.build_split();
+    let request = request.expect("failed to build request");
+    let mut rate_limited = 0;
+    loop {
+        // the request is kept around in case it has to be sent again after being rate limited
+        let attempt = request.try_clone().expect("failed to clone request");
+        info!("{} {}", attempt.method(), attempt.url());
+        let res = match client.execute(attempt) {
+            Ok(res) => res,
+            Err(err) if err.is_timeout() => {
+                return Err(format!(
+                    "request to GitLab timed out after {} seconds",
+                    TIMEOUT_SECS.load(Ordering::Relaxed)
+                ))
+            }
+            Err(err) => {
+                let proxy = PROXY.get().and_then(|proxy| proxy.as_deref());
+                return Err(match proxy {
+                    Some(proxy) if err.is_connect() => format!(
+                        "failed to connect to GitLab through the proxy {}: {}",
+                        proxy,
+                        error_chain(&err)
+                    ),
+                    _ => format!("failed to execute request: {}", error_chain(&err)),
+                });
+            }
+        };
+        info!("-> {}", res.status());
+
+        if res.status() != StatusCode::TOO_MANY_REQUESTS || rate_limited >= MAX_RATE_LIMITED_RETRIES
+        {
+            return Ok(res);
+        }
+        rate_limited += 1;
+
+        // retry-after is usually a number of seconds, fall back to waiting longer each time otherwise
+        let wait = res
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .unwrap_or(1 << rate_limited);
+        warn!("rate limited by GitLab, retrying in {} seconds", wait);
+        thread::sleep(Duration::from_secs(wait));
+    }
+}
+
+// retries the request when it fails in a way that might go away by itself (network errors, 5xx)
+fn send_with_retries(request: RequestBuilder) -> Result<Response, String> {
+    let mut attempt = 1;
+    loop {
+        let result = try_send(request.try_clone().expect("failed to clone request"));
+        let transient = match &result {
+            Ok(res) => res.status().is_server_error(),
+            Err(_) => true,
+        };
+        if !transient || attempt >= MAX_TRANSIENT_RETRIES {
+            return result;
+        }
+        warn!(
+            "request failed, retrying ({}/{})",
+            attempt, MAX_TRANSIENT_RETRIES
+        );
+        thread::sleep(Duration::from_secs(1 << attempt));
+        attempt += 1;
+    }
+}
+
+// reqwest errors only say which request failed, the actual cause (tls, dns...) is in their sources
+fn error_chain(err: &dyn Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+        source = err.source();
+    }
+    message
+}
+
+fn send(request: RequestBuilder) -> Response {
+    // there is no response to take the url from when the request does not go through
+    let url = request
+        .try_clone()
+        .and_then(|request| request.build().ok())
+        .map(|request| request.url().to_string());
+    match try_send(request) {
+        Ok(res) => res,
+        Err(err) => report_error(
+            EXIT_NETWORK,
+            ErrorReport {
+                kind: "network",
+                message: err,
+                status_code: None,
+                url,
+            },
+        ),
+    }
+}
+
+fn read_body(res: Response) -> String {
+    // a connection cut in the middle of the body ends up here
+    let body = res.text().unwrap_or_else(|err| {
+        fail(
+            EXIT_NETWORK,
+            format!("failed to read the response: {}", error_chain(&err)),
+        )
+    });
+    if body.chars().count() > BODY_LOG_LENGTH {
+        let truncated: String = body.chars().take(BODY_LOG_LENGTH).collect();
+        debug!("{}...", truncated);
+    } else {
+        debug!("{}", body);
+    }
+    body
+}
+
+// turns a failed response into a readable message
+fn check_status(res: Response, action: &str) -> Result<Response, String> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+
+    if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+        return Err("Authentication failed — check your token".to_string());
+    }
+
+    let is_html = is_html(&res);
+    let body = read_body(res);
+    let mut message = format!(
+        "Failed to {} : {} ({})",
+        action,
+        status.canonical_reason().unwrap_or("Unknown"),
+        status.as_str()
+    );
+    push_body_excerpt(&mut message, &body, is_html);
+    Err(message)
+}
+
+fn is_html(res: &Response) -> bool {
+    res.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("text/html"))
+}
+
+// error pages of proxies and sso portals can be whole html documents, only their start is kept
+fn push_body_excerpt(message: &mut String, body: &str, is_html: bool) {
+    if is_html || body.trim_start().starts_with('<') {
+        message.push_str("\nthe server returned html, are you behind an sso portal or a proxy ?");
+    }
+    if !body.is_empty() {
+        message.push('\n');
+        message.extend(body.chars().take(BODY_LOG_LENGTH));
+    }
+}
+
+// exits with a readable message if the request failed
+fn check_response(res: Response, action: &str) -> Response {
+    let status = res.status();
+    let url = res.url().to_string();
+    match check_status(res, action) {
+        Ok(res) => res,
+        Err(err) => {
+            let auth_failed = status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN;
+            report_error(
+                if auth_failed { EXIT_CONFIG } else { EXIT_ERROR },
+                ErrorReport {
+                    kind: if auth_failed { "auth" } else { "http" },
+                    message: err,
+                    status_code: Some(status.as_u16()),
+                    url: Some(url),
+                },
+            );
+        }
+    }
+}
+
+fn fetch<T>(client: &Client, url: String, token: &str, action: &str) -> T
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let res = send(add_auth(client.get(url), token));
+
+    let res = check_response(res, action);
+
+    parse_body(res, action)
+}
+
+// an sso portal or a proxy can answer with an html page instead of json
+fn parse_body<T>(res: Response, action: &str) -> T
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let status = res.status();
+    let url = res.url().to_string();
+    let is_html = is_html(&res);
+    let body = read_body(res);
+    match serde_json::from_str::<T>(&body) {
+        Ok(value) => value,
+        Err(err) => {
+            let mut message = format!(
+                "Failed to {} : the response ({}) is not valid json: {}",
+                action,
+                status.as_str(),
+                err
+            );
+            push_body_excerpt(&mut message, &body, is_html);
+            report_error(
+                EXIT_ERROR,
+                ErrorReport {
+                    kind: "http",
+                    message,
+                    status_code: Some(status.as_u16()),
+                    url: Some(url),
+                },
+            );
+        }
+    }
+}
+
+// gitlab versions before 11 ignore assignee_ids and need assignee_id instead
+// (major, minor), None when the instance does not tell its version
+fn fetch_gitlab_version(client: &Client, gitlab_domain: &str, token: &str) -> Option<(u32, u32)> {
+    let res = try_send(add_auth(
+        client.get(format!("{}/api/v4/version", gitlab_domain)),
+        token,
+    ))
+    .and_then(|res| check_status(res, "get the gitlab version"))
+    .ok()?;
+    let version = serde_json::from_str::<GitlabVersion>(&read_body(res)).ok()?;
+    info!("gitlab version {}", version.version);
+    let mut parts = version
+        .version
+        .split(['.', '-'])
+        .map(|part| part.parse::<u32>().ok());
+    Some((parts.next()??, parts.next()??))
+}
+
+fn fetch_current_user(client: &Client, gitlab_domain: &str, token: &str) -> GitlabUser {
+    fetch(
+        client,
+        format!("{}/api/v4/user", gitlab_domain),
+        token,
+        "get the current user",
+    )
+}
+
+fn page_fetch<T>(client: &Client, url: String, token: &str) -> Vec<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let mut results = Vec::new();
+    let mut page = 1;
+    loop {
+        let res = send(add_auth(
+            client.get(
+                Url::parse_with_params(
+                    &url,
+                    &[("per_page", "100"), ("page", page.to_string().as_str())],
+                )
+                .unwrap(),
+            ),
+            token,
+        ));
+
+        let res = check_response(res, "fetch data from gitlab");
+
+        let page_issues: Vec<T> = parse_body(res, "fetch data from gitlab");
+
+        if page_issues.is_empty() {
+            return results;
+        }
+        results.extend(page_issues);
+        page += 1;
+    }
+}
+
+// only the X-Total header of a single issue page is read, gitlab leaves it out past 10000 issues
+fn count_issues(client: &Client, url: String, token: &str) -> Option<usize> {
+    let url = Url::parse_with_params(&url, &[("per_page", "1")]).unwrap();
+    let res = try_send(add_auth(client.get(url), token)).ok()?;
+    if !res.status().is_success() {
+        return None;
+    }
+    res.headers().get("x-total")?.to_str().ok()?.parse().ok()
+}
+
+// keys of a profile that are left out when the args set their own
+const SELECTION_KEYS: [&str; 7] = [
+    "issues",
+    "milestones",
+    "milestones_contains",
+    "selection",
+    "iteration",
+    "labels",
+    "search",
+];
+const BALANCING_KEYS: [&str; 4] = ["mode", "balance_by", "spread_by_age", "by_priority"];
+
+fn main() -> Result<(), ConfigError> {
+    let cli = Cli::parse();
+
+    let log_level = match cli.verbose {
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    // NO_COLOR only counts when set to a non empty value, see https://no-color.org
+    let no_color = cli.no_color || env::var("NO_COLOR").is_ok_and(|val| !val.is_empty());
+    NO_COLOR.store(no_color, Ordering::Relaxed);
+    JSON_ERRORS.store(
+        matches!(cli.error_format, ErrorFormat::Json),
+        Ordering::Relaxed,
+    );
+
+    env_logger::Builder::new()
+        .filter_module(module_path!(), log_level)
+        .write_style(if no_color {
+            WriteStyle::Never
+        } else {
+            WriteStyle::Auto
+        })
+        .format_timestamp(None)
+        .init();
+
+    let command = cli.command.unwrap_or(Command::Roll(Box::new(cli.roll)));
+    let roll_args = match &command {
+        Command::Roll(args) => Some(args),
+        _ => None,
+    };
+
+    let config_file = cli.config_file;
+
+    let mut builder = Config::builder();
+    // the user level config comes first so the local one overrides it
+    if let Some(global_config_file) = global_config_file() {
+        if global_config_file.exists() {
+            builder = builder.add_source(File::from(global_config_file).format(FileFormat::Toml));
+        }
+    }
+    let config_format = cli
+        .config_format
+        .unwrap_or_else(|| ConfigFormat::from_path(&config_file));
+    if fs::exists(&config_file).expect("failed to check for config file") {
+        builder = builder.add_source(File::new(&config_file, FileFormat::from(config_format)));
+    }
+    let token_file = cli.token_file.map(|path| match fs::read_to_string(&path) {
+        Ok(content) => content.trim().to_string(),
+        Err(err) => {
+            fail(
+                EXIT_CONFIG,
+                format!("failed to read the token file \"{}\": {}", path, err),
+            );
+        }
+    });
+
+    // the profile sits between the config file and the args
+    if let Some((args, profile)) = roll_args.and_then(|args| Some((args, args.profile.as_ref()?))) {
+        let file_config = match builder.clone().build() {
+            Ok(config) => config,
+            Err(err) => {
+                fail(EXIT_CONFIG, format!("failed to read the config: {}", err));
+            }
+        };
+        let Ok(profile_table) = file_config.get_table(&format!("profiles.{}", profile)) else {
+            fail(
+                EXIT_CONFIG,
+                format!("no profile named \"{}\" in the config file", profile),
+            );
+        };
+        for (key, value) in profile_table {
+            if args.selects_issues() && SELECTION_KEYS.contains(&key.as_str())
+                || args.balances_issues() && BALANCING_KEYS.contains(&key.as_str())
+            {
+                continue;
+            }
+            builder = builder.set_override(key, value)?;
+        }
+    }
+
+    //  .add_async_source(...)
+    // later overrides replace earlier ones, so the token goes from lowest to highest precedence
+    builder = builder
+        .set_override_option("url", cli.url)?
+        .set_override_option("group", cli.group)?
+        .set_override_option("project_id", cli.project_id)?
+        .set_override_option("cache_ttl", cli.cache_ttl)?
+        .set_override_option("no_cache", cli.no_cache.then_some(true))?
+        .set_override_option("refresh", cli.refresh.then_some(true))?
+        .set_override_option("token", env::var("GITLAB_TOKEN").ok())?
+        .set_override_option("token", token_file)?
+        .set_override_option("token", cli.token)?
+        .set_override_option(
+            "auth_type",
+            cli.auth_type.map(|auth_type| auth_type.to_string()),
+        )?
+        .set_override_option(
+            "issues",
+            roll_args.and_then(|args| args.issues.as_deref().map(parse_issue_ids)),
+        )?
+        .set_override_option("members", roll_args.and_then(|args| args.members.clone()))?
+        .set_override_option("exclude", roll_args.and_then(|args| args.exclude.clone()))?
+        .set_override_option("projects", roll_args.and_then(|args| args.projects.clone()))?
+        .set_override_option("webhook", roll_args.and_then(|args| args.webhook.clone()))?
+        .set_override_option(
+            "milestones",
+            roll_args.and_then(|args| args.milestones.clone()),
+        )?
+        .set_override_option(
+            "milestones_contains",
+            roll_args.and_then(|args| args.milestones_contains.clone()),
+        )?
+        .set_override_option(
+            "iteration",
+            roll_args.and_then(|args| args.iteration.clone().map(|iteration| vec![iteration])),
+        )?
+        .set_override_option("search", roll_args.and_then(|args| args.search.clone()))?
+        .set_override_option(
+            "state",
+            roll_args.and_then(|args| args.state.map(|state| state.to_string())),
+        )?
+        .set_override_option(
+            "unassigned_only",
+            roll_args.and_then(|args| args.unassigned_only.then_some(true)),
+        )?
+        .set_override_option(
+            "mode",
+            roll_args.and_then(|args| args.mode.map(|mode| mode.to_string())),
+        )?
+        .set_override_option(
+            "balance_by",
+            roll_args.and_then(|args| {
+                args.balance_by
+                    .or(args.by_weight.then_some(BalanceBy::Weight))
+                    .map(|balance_by| balance_by.to_string())
+            }),
+        )?
+        .set_override_option(
+            "spread_by_age",
+            roll_args.and_then(|args| args.spread_by_age.then_some(true)),
+        )?
+        .set_override_option(
+            "by_priority",
+            roll_args.and_then(|args| args.by_priority.then_some(true)),
+        )?
+        .set_override_option(
+            "types",
+            roll_args.and_then(|args| {
+                args.types
+                    .as_ref()
+                    .map(|types| types.iter().map(|t| t.to_string()).collect::<Vec<_>>())
+            }),
+        )?;
+
+    let config = match builder.build() {
+        Ok(config) => config,
+        Err(err) => {
+            fail(EXIT_CONFIG, format!("failed to read the config: {}", err));
+        }
+    };
+
+    let url = config.get_string("url");
+    let token = config.get_string("token");
+
+    if url.is_err() {
+        fail(
+            EXIT_CONFIG,
+            "Please add a url to the config file or using the --url argument".to_string(),
+        );
+    }
+
+    let url = url.unwrap();
+    let url_parse = Url::parse(&url);
+    if url_parse.is_err() {
+        fail(EXIT_CONFIG, format!("the url \"{}\" is not valid", url));
+    }
+    let url_parse = url_parse.unwrap();
+
+    let gitlab_domain = format!(
+        "{}://{}",
+        url_parse.scheme(),
+        url_parse
+            .domain()
+            .expect("failed to extract the domain out of the url")
+    );
+
+    if token.is_err() {
+        fail(EXIT_CONFIG, "Please add a token to the config file, the GITLAB_TOKEN environment variable or using the --token or --token-file arguments".to_string());
+    }
+
+    let token = token.unwrap();
+
+    let auth_type = match config.get_string("auth_type") {
+        Ok(auth_type) => match AuthType::from_str(&auth_type, true) {
+            Ok(auth_type) => auth_type,
+            Err(_) => {
+                fail(
+                    EXIT_CONFIG,
+                    format!(
+                        "unknown auth type \"{}\", expected private or oauth",
+                        auth_type
+                    ),
+                );
+            }
+        },
+        Err(_) => AuthType::Private,
+    };
+    AUTH_TYPE.set(auth_type).unwrap();
+
+    TIMEOUT_SECS.store(cli.timeout, Ordering::Relaxed);
+    let mut client_builder = Client::builder()
+        .timeout(Duration::from_secs(cli.timeout))
+        .danger_accept_invalid_certs(cli.insecure);
+    // without --proxy, reqwest already uses HTTPS_PROXY, HTTP_PROXY, ALL_PROXY and NO_PROXY
+    let proxy_url = cli.proxy.clone().or_else(|| {
+        [
+            "HTTPS_PROXY",
+            "https_proxy",
+            "HTTP_PROXY",
+            "http_proxy",
+            "ALL_PROXY",
+            "all_proxy",
+        ]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|val| !val.is_empty()))
+    });
+    if let Some(proxy) = &cli.proxy {
+        let proxy = match Proxy::all(proxy) {
+            Ok(proxy) => proxy.no_proxy(NoProxy::from_env()),
+            Err(err) => {
+                fail(
+                    EXIT_CONFIG,
+                    format!("the proxy url \"{}\" is not valid: {}", proxy, err),
+                );
+            }
+        };
+        client_builder = client_builder.proxy(proxy);
+    }
+    PROXY.set(proxy_url).unwrap();
+    if let Some(ca_cert) = &cli.ca_cert {
+        let pem = match fs::read(ca_cert) {
+            Ok(pem) => pem,
+            Err(err) => {
+                fail(
+                    EXIT_CONFIG,
+                    format!("failed to read the certificate \"{}\": {}", ca_cert, err),
+                );
+            }
+        };
+        let certificate = match Certificate::from_pem(&pem) {
+            Ok(certificate) => certificate,
+            Err(err) => {
+                fail(
+                    EXIT_CONFIG,
+                    format!("the certificate \"{}\" is not valid: {}", ca_cert, err),
+                );
+            }
+        };
+        client_builder = client_builder.add_root_certificate(certificate);
+    }
+    let client = client_builder
+        .build()
+        .expect("failed to build the http client");
+
+    let user = fetch_current_user(&client, &gitlab_domain, &token);
+
+    let gitlab_version = fetch_gitlab_version(&client, &gitlab_domain, &token);
+    GITLAB_VERSION.set(gitlab_version).unwrap();
+    if let Some((major, minor)) = gitlab_version.filter(|&version| version < MIN_GITLAB_VERSION) {
+        eprintln!(
+            "Warning: GitLab {}.{} is older than {}.{}, some features may not work",
+            major, minor, MIN_GITLAB_VERSION.0, MIN_GITLAB_VERSION.1
+        );
+    }
+    // gitlab only knows assignee_ids since 11.0
+    let legacy_assign = cli.legacy_assign || gitlab_version.is_some_and(|(major, _)| major < 11);
+    LEGACY_ASSIGN.store(legacy_assign, Ordering::Relaxed);
+
+    match command {
+        Command::Roll(args) => {
+            if !(args.dry_run && matches!(args.output_format, OutputFormat::Json)) {
+                println!("Logged in as {}", user.username);
+            }
+            roll(
+                &args,
+                &config,
+                &client,
+                &gitlab_domain,
+                &token,
+                &url,
+                &cli.history_file,
+                &config_file,
+                config_format,
+            );
+        }
+        Command::Undo => undo(&client, &gitlab_domain, &token, &cli.history_file),
+        Command::Whoami => whoami(&client, &gitlab_domain, &token, &user),
+        Command::ListMembers => list_members(&config, &client, &gitlab_domain, &token, &url),
+    }
+
+    Ok(())
+}
+
+// XDG_CONFIG_HOME is honored everywhere, otherwise this is ~/.config on linux,
+// ~/Library/Application Support on macos and %APPDATA% on windows
+fn global_config_file() -> Option<PathBuf> {
+    let config_dir = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => dirs::config_dir()?,
+    };
+    Some(config_dir.join("gitlab-roulette").join("config.toml"))
+}
+
+// same as the config, ~/.cache on linux, ~/Library/Caches on macos and %LOCALAPPDATA% on windows
+fn projects_cache_file() -> Option<PathBuf> {
+    let cache_dir = match env::var("XDG_CACHE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => dirs::cache_dir()?,
+    };
+    Some(cache_dir.join("gitlab-roulette").join("projects.json"))
+}
+
+// the projects list is cached per instance since it can take a while to fetch on big instances
+fn fetch_projects(
+    config: &Config,
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+) -> Vec<GitlabProject> {
+    let use_cache = !config.get_bool("no_cache").unwrap_or(false);
+    let refresh = config.get_bool("refresh").unwrap_or(false);
+    let ttl = config
+        .get_int("cache_ttl")
+        .map(|ttl| ttl as u64)
+        .unwrap_or(DEFAULT_CACHE_TTL_SECS);
+    let cache_file = projects_cache_file().filter(|_| use_cache);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is before the unix epoch")
+        .as_secs();
+
+    // a missing or broken cache is simply refetched
+    let mut cache: HashMap<String, CachedProjects> = cache_file
+        .as_ref()
+        .and_then(|file| fs::read_to_string(file).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+
+    if !refresh {
+        if let Some(cached) = cache.remove(gitlab_domain) {
+            if now.saturating_sub(cached.timestamp) < ttl {
+                info!("using the cached projects list");
+                return cached.projects;
+            }
+        }
+    }
+
+    let projects: Vec<GitlabProject> = page_fetch(
+        client,
+        format!(
+            "{}/api/v4/projects?membership=true&simple=true",
+            gitlab_domain
+        ),
+        token,
+    );
+
+    if let Some(cache_file) = cache_file {
+        cache.insert(
+            gitlab_domain.to_string(),
+            CachedProjects {
+                timestamp: now,
+                projects,
+            },
+        );
+        let written = cache_file
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| {
+                fs::write(
+                    &cache_file,
+                    serde_json::to_string(&cache).expect("failed to serialize projects cache"),
+                )
+            });
+        if let Err(err) = written {
+            eprintln!("Warning: failed to write the projects cache: {}", err);
+        }
+        return cache.remove(gitlab_domain).unwrap().projects;
+    }
+
+    projects
+}
+
+fn fetch_members(
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    scope: &str,
+) -> Vec<GitlabProjectMember> {
+    let members: Vec<GitlabProjectMember> = page_fetch(
+        client,
+        format!("{}/api/v4/{}/members", gitlab_domain, scope),
+        token,
+    );
+    dedup_members(members)
+}
+
+// None when there is no current milestone, or too many to pick one
+fn current_milestone(
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    scopes: &[Scope],
+) -> Option<GitlabMilestone> {
+    // project issues often use the milestones of the parent groups, the parameter was renamed in 16.7
+    let ancestors = match GITLAB_VERSION.get().copied().flatten() {
+        Some(version) if version < (16, 7) => "&include_parent_milestones=true",
+        _ => "&include_ancestors=true",
+    };
+    let mut current: Vec<GitlabMilestone> = Vec::new();
+    for scope in scopes {
+        let url = match scope {
+            Scope::Project(_) => format!(
+                "{}/api/v4/{}/milestones?state=active{}",
+                gitlab_domain,
+                scope.path(),
+                ancestors
+            ),
+            Scope::Group(_) => format!(
+                "{}/api/v4/{}/milestones?state=active",
+                gitlab_domain,
+                scope.path()
+            ),
+        };
+        // the projects of a roll can share the milestones of a group
+        for milestone in page_fetch::<GitlabMilestone>(client, url, token) {
+            if milestone.is_current() && !current.contains(&milestone) {
+                current.push(milestone);
+            }
+        }
+    }
+    match current.len() {
+        0 => {
+            eprintln!("Note: no milestone is currently running, select the milestones instead");
+            None
+        }
+        1 => current.pop(),
+        _ => {
+            let titles: Vec<&str> = current.iter().map(|m| m.title.as_str()).collect();
+            eprintln!(
+                "Note: several milestones are currently running ({}), select the milestones instead",
+                titles.join(", ")
+            );
+            None
+        }
+    }
+}
+
+fn issues_url(
+    gitlab_domain: &str,
+    scope: &Scope,
+    state: IssueState,
+    search: Option<&str>,
+) -> String {
+    let mut url = Url::parse(&format!("{}/api/v4/{}/issues", gitlab_domain, scope.path())).unwrap();
+    if !matches!(state, IssueState::All) {
+        url.query_pairs_mut()
+            .append_pair("state", &state.to_string());
+    }
+    if let Some(term) = search {
+        url.query_pairs_mut().append_pair("search", term);
+    }
+    url.to_string()
+}
+
+// the search is done by gitlab, the results are then matched against the issues that passed the filters
+fn search_issues<'a>(
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    scopes: &[Scope],
+    state: IssueState,
+    term: &str,
+    issues: &'a [GitlabIssue],
+) -> Vec<&'a GitlabIssue> {
+    let found: Vec<GitlabIssue> = scopes
+        .iter()
+        .flat_map(|scope| {
+            page_fetch::<GitlabIssue>(
+                client,
+                issues_url(gitlab_domain, scope, state, Some(term)),
+                token,
+            )
+        })
+        .collect();
+    let selected_issues: Vec<&GitlabIssue> = issues
+        .iter()
+        .filter(|issue| {
+            found
+                .iter()
+                .any(|other| other.project_id == issue.project_id && other.iid == issue.iid)
+        })
+        .collect();
+    status!(
+        "{} issues found matching \"{}\"",
+        selected_issues.len(),
+        term
+    );
+    selected_issues
+}
+
+// one username or user id per line, the users do not have to be members of the project
+fn fetch_member_file(
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    path: &str,
+) -> Vec<GitlabProjectMember> {
+    let content = fs::read_to_string(path).unwrap_or_else(|err| {
+        fail(
+            EXIT_CONFIG,
+            format!("failed to read the member file \"{}\": {}", path, err),
+        )
+    });
+
+    let mut users = Vec::new();
+    let mut unknown = Vec::new();
+    for line in content.lines() {
+        let line = line.trim().trim_start_matches('@');
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let user = if line.chars().all(|c| c.is_ascii_digit()) {
+            let res = send(add_auth(
+                client.get(format!("{}/api/v4/users/{}", gitlab_domain, line)),
+                token,
+            ));
+            if res.status() == StatusCode::NOT_FOUND {
+                None
+            } else {
+                Some(parse_body(check_response(res, "get a user"), "get a user"))
+            }
+        } else {
+            let res = send(add_auth(
+                client
+                    .get(format!("{}/api/v4/users", gitlab_domain))
+                    .query(&[("username", line)]),
+                token,
+            ));
+            let found: Vec<GitlabProjectMember> =
+                parse_body(check_response(res, "get a user"), "get a user");
+            found.into_iter().next()
+        };
+        match user {
+            Some(user) => users.push(user),
+            None => unknown.push(line.to_string()),
+        }
+    }
+
+    if !unknown.is_empty() {
+        fail(
+            EXIT_CONFIG,
+            format!(
+                "the following users of \"{}\" cannot be found: {}",
+                path,
+                unknown.join(", ")
+            ),
+        );
+    }
+    dedup_members(users)
+}
+
+// the same user can be listed several times (e.g. direct and inherited membership)
+fn dedup_members(members: Vec<GitlabProjectMember>) -> Vec<GitlabProjectMember> {
+    let mut seen: HashSet<i32> = HashSet::new();
+    members
+        .into_iter()
+        .filter(|member| seen.insert(member.id))
+        .collect()
+}
+
+fn select_scope(
+    config: &Config,
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    url: &str,
+    interactive: bool,
+    pick_project: bool,
+) -> (Scope, bool) {
+    if let Ok(group_path) = config.get_string("group") {
+        let group: GitlabGroup = fetch(
+            client,
+            format!(
+                "{}/api/v4/groups/{}",
+                gitlab_domain,
+                form_urlencoded::byte_serialize(group_path.as_bytes()).collect::<String>()
+            ),
+            token,
+            "get the group",
+        );
+        status!("Found group: {}", group.full_path);
+        (Scope::Group(group), false)
+    } else if let Ok(project_id) = config.get_int("project_id") {
+        let res = send(add_auth(
+            client.get(format!("{}/api/v4/projects/{}", gitlab_domain, project_id)),
+            token,
+        ));
+        if res.status() == StatusCode::NOT_FOUND {
+            fail(
+                EXIT_CONFIG,
+                format!(
+                "no project with the id {} was found, or it cannot be accessed using your token",
+                project_id
+            ),
+            );
+        }
+        let project: GitlabProject =
+            parse_body(check_response(res, "get the project"), "get the project");
+        status!("Found project: {}", project.name);
+        (Scope::Project(project), false)
+    } else {
+        let projects = fetch_projects(config, client, gitlab_domain, token);
+
+        // try to find the project using URL, unless going back to the project prompt
+        let project = projects
+            .iter()
+            .find(|p| p.web_url == url)
+            .filter(|_| !pick_project);
+        let project = if let Some(project) = project {
+            status!("Found project: {}", project.name);
+            return (Scope::Project(project.clone()), false);
+        } else if !interactive {
+            fail(EXIT_CONFIG, format!("no project found at \"{}\"", url));
+        } else {
+            let projects_names: Vec<String> = projects
+                .iter()
+                .map(|proj| proj.path_with_namespace.clone())
+                .collect();
+
+            let selection = FuzzySelect::with_theme(&*theme())
+                .with_prompt("Select a project (type to search): ")
+                .items(&projects_names)
+                .interact()
+                .unwrap();
+
+            &projects[selection]
+        };
+        (Scope::Project(project.clone()), true)
+    }
+}
+
+fn fetch_roll_issues(
+    args: &RollArgs,
+    config: &Config,
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    scopes: &[Scope],
+) -> Vec<GitlabIssue> {
+    let issue_state = config_enum(config, "state").unwrap_or(IssueState::Opened);
+    // filtered and searched by gitlab, old projects can have a lot of closed issues
+    let search = config.get_string("search").ok();
+    let mut issues: Vec<GitlabIssue> = scopes
+        .iter()
+        .flat_map(|scope| {
+            page_fetch::<GitlabIssue>(
+                client,
+                issues_url(gitlab_domain, scope, issue_state, search.as_deref()),
+                token,
+            )
+        })
+        .collect();
+    if let Some(term) = &search {
+        status!("{} issues found matching \"{}\"", issues.len(), term);
+    }
+
+    let other_state = match issue_state {
+        IssueState::Opened => Some(IssueState::Closed),
+        IssueState::Closed => Some(IssueState::Opened),
+        IssueState::All => None,
+    };
+    if let Some(other_state) = other_state {
+        let filtered: usize = scopes
+            .iter()
+            .filter_map(|scope| {
+                count_issues(
+                    client,
+                    issues_url(gitlab_domain, scope, other_state, search.as_deref()),
+                    token,
+                )
+            })
+            .sum();
+        if filtered > 0 {
+            status!(
+                "{} issues filtered out because they are not {}",
+                filtered,
+                issue_state
+            );
+        }
+    }
+
+    if !args.include_confidential {
+        let issue_count = issues.len();
+        issues.retain(|issue| !issue.confidential);
+        if issues.len() != issue_count {
+            status!(
+                "{} confidential issues skipped, use --include-confidential to assign them",
+                issue_count - issues.len()
+            );
+        }
+    }
+
+    let exclude_label = config
+        .get_string("exclude_label")
+        .unwrap_or(DEFAULT_EXCLUDE_LABEL.to_string());
+    let issue_count = issues.len();
+    issues.retain(|issue| !issue.labels.contains(&exclude_label));
+    if issues.len() != issue_count {
+        status!(
+            "{} issues skipped because they have the {} label",
+            issue_count - issues.len(),
+            exclude_label
+        );
+    }
+
+    if config.get_bool("unassigned_only").unwrap_or(false) {
+        let issue_count = issues.len();
+        issues.retain(|issue| issue.assignees.is_empty());
+        if issues.len() != issue_count {
+            status!(
+                "{} issues skipped because they are already assigned",
+                issue_count - issues.len()
+            );
+        }
+    }
+
+    if issues.is_empty() {
+        status!("This project has no issues to assign");
+        process::exit(0);
+    }
+
+    if args.check_write {
+        check_write(client, gitlab_domain, token, &issues[0], args.dry_run);
+    }
+
+    args.sort.sort(&mut issues);
+    issues
+}
+
+fn fetch_roll_members(
+    args: &RollArgs,
+    config: &Config,
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    scopes: &[Scope],
+) -> Vec<GitlabProjectMember> {
+    let mut members = fetch_members(client, gitlab_domain, token, &scopes[0].path());
+    // an issue can only be assigned to members of its project
+    let member_count = members.len();
+    for scope in &scopes[1..] {
+        let others = fetch_members(client, gitlab_domain, token, &scope.path());
+        members.retain(|member| others.iter().any(|other| other.id == member.id));
+    }
+    if members.len() != member_count {
+        status!(
+            "{} members filtered out because they are not part of every project",
+            member_count - members.len()
+        );
+    }
+
+    if let Some(member_file) = &args.member_file {
+        for user in fetch_member_file(client, gitlab_domain, token, member_file) {
+            if !members.iter().any(|member| member.id == user.id) {
+                members.push(user);
+            }
+        }
+    }
+
+    // the rotation restricts the members first, the excluded ones are then removed from it
+    if let Some(rotation) = config_strings(config, "rotation") {
+        members.retain(|member| rotation.contains(&member.username));
+    }
+
+    if let Some(config_exclude) = config_strings(config, "exclude") {
+        members.retain(|member| !config_exclude.contains(&member.username));
+    }
+    members
+}
+
+// escape on the first prompt
+fn nothing_to_go_back() -> ! {
+    println!("Exiting");
+    process::exit(EXIT_CANCELLED);
+}
+
+// the same seed always gives the same assignements for the same issues and members
+#[allow(clippy::too_many_arguments)]
+fn draw(
+    args: &RollArgs,
+    mode: AssignMode,
+    balance_by: BalanceBy,
+    spread_by_age: bool,
+    weights: &[i32],
+    selected_issues: &[&GitlabIssue],
+    member_count: usize,
+    limits: MemberLimits,
+    current_assignees: &[Vec<usize>],
+    rng: &mut impl Rng,
+) -> Vec<Vec<usize>> {
+    let assignees_per_issue = args.assignees_per_issue;
+    let mut assignements = if balance_by == BalanceBy::Both {
+        distribute_by_count_and_weight(weights, member_count, assignees_per_issue, limits, rng)
+    } else if balance_by == BalanceBy::Weight {
+        distribute_by_weight(weights, member_count, assignees_per_issue, limits, rng)
+    } else if spread_by_age {
+        let created_at: Vec<i64> = selected_issues
+            .iter()
+            .map(|issue| issue.created_at.timestamp())
+            .collect();
+        distribute_by_age(&created_at, member_count, assignees_per_issue, rng)
+    } else if let AssignMode::RoundRobin = mode {
+        distribute_round_robin(selected_issues.len(), member_count, assignees_per_issue)
+    } else {
+        distribute(
+            selected_issues.len(),
+            member_count,
+            assignees_per_issue,
+            rng,
+        )
+    };
+
+    if args.avoid_current {
+        let stuck = avoid_current_assignees(
+            &mut assignements,
+            current_assignees,
+            member_count,
+            limits,
+            rng,
+        );
+        if stuck > 0 {
+            eprintln!(
+                "Warning: {} issues could only be given back to one of their current assignees",
+                stuck
+            );
+        }
+    }
+    assignements
+}
+
+// the members taking part in a roll and their first draw
+struct RollDraw<'a> {
+    members: Vec<&'a GitlabProjectMember>,
+    // indices in members of the members each issue is currently assigned to
+    current_assignees: Vec<Vec<usize>>,
+    assignements: Vec<Vec<usize>>,
+}
+
+// the draw only depends on the seed, not on the order the members are listed in
+#[allow(clippy::too_many_arguments)]
+fn draw_roll<'a>(
+    args: &RollArgs,
+    mode: AssignMode,
+    balance_by: BalanceBy,
+    spread_by_age: bool,
+    weights: &[i32],
+    selected_issues: &[&GitlabIssue],
+    mut members: Vec<&'a GitlabProjectMember>,
+    limits: MemberLimits,
+    rng: &mut impl Rng,
+) -> RollDraw<'a> {
+    members.sort_by_key(|member| member.id);
+
+    if let Some(limit) = args.limit_members {
+        if limit > members.len() {
+            eprintln!(
+                "Warning: only {} members are selected, using all of them instead of {}",
+                members.len(),
+                limit
+            );
+        } else {
+            let mut picked = rand::seq::index::sample(rng, members.len(), limit).into_vec();
+            picked.sort_unstable();
+            members = picked.into_iter().map(|i| members[i]).collect();
+            let usernames: Vec<&str> = members
+                .iter()
+                .map(|member| member.username.as_str())
+                .collect();
+            status!("Drawn members: {}", usernames.join(", "));
+        }
+    }
+
+    if args.assignees_per_issue > members.len() {
+        fail(
+            EXIT_ERROR,
+            format!(
+                "cannot assign {} members per issue with only {} members selected",
+                args.assignees_per_issue,
+                members.len()
+            ),
+        );
+    }
+    if let Err(err) = limits.check(
+        selected_issues.len(),
+        members.len(),
+        args.assignees_per_issue,
+    ) {
+        fail(EXIT_ERROR, err);
+    }
+
+    let current_assignees: Vec<Vec<usize>> = selected_issues
+        .iter()
+        .map(|issue| {
+            (0..members.len())
+                .filter(|&m| {
+                    issue
+                        .assignees
+                        .iter()
+                        .any(|assignee| assignee.id == members[m].id)
+                })
+                .collect()
+        })
+        .collect();
+    let assignements = draw(
+        args,
+        mode,
+        balance_by,
+        spread_by_age,
+        weights,
+        selected_issues,
+        members.len(),
+        limits,
+        &current_assignees,
+        rng,
+    );
+    RollDraw {
+        members,
+        current_assignees,
+        assignements,
+    }
+}
+
+// the other projects of the config are pooled with the first one
+fn add_other_projects(
+    config: &Config,
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    scopes: &mut Vec<Scope>,
+) {
+    // the other projects are never prompted for, they have to be found by their url
+    if let Ok(project_urls) = config.get_array("projects") {
+        let projects = fetch_projects(config, client, gitlab_domain, token);
+        for project_url in project_urls {
+            let project_url = project_url
+                .into_string()
+                .expect("provided project url is not a string");
+            let Some(project) = projects.iter().find(|p| p.web_url == project_url) else {
+                fail(
+                    EXIT_CONFIG,
+                    format!("no project found at \"{}\"", project_url),
+                );
+            };
+            let scope = Scope::Project(project.clone());
+            if scopes.iter().any(|other| other.path() == scope.path()) {
+                continue;
+            }
+            status!("Found project: {}", project.name);
+            scopes.push(scope);
+        }
+    }
+}
+
+// the types and --count only apply to the issues that were selected
+fn narrow_issues(
+    args: &RollArgs,
+    types: &Option<Vec<IssueType>>,
+    selected_issues: &mut Vec<&GitlabIssue>,
+    rng: &mut impl Rng,
+) {
+    if let Some(types) = types {
+        let mut filtered: Vec<(String, usize)> = Vec::new();
+        selected_issues.retain(|issue| {
+            let keep = types.iter().any(|t| t.matches(issue));
+            if !keep {
+                match filtered.iter_mut().find(|(t, _)| *t == issue.r#type) {
+                    Some((_, count)) => *count += 1,
+                    None => filtered.push((issue.r#type.clone(), 1)),
+                }
+            }
+            keep
+        });
+        for (issue_type, count) in filtered {
+            status!(
+                "{} issues of type {} filtered out",
+                count,
+                issue_type.to_lowercase()
+            );
+        }
+    }
+
+    if let Some(count) = args.count {
+        if count > selected_issues.len() {
+            eprintln!(
+                "Warning: only {} issues are selected, using all of them instead of {}",
+                selected_issues.len(),
+                count
+            );
+        } else {
+            // sampled by index so the issues stay in the order they are sorted in
+            let mut picked = rand::seq::index::sample(rng, selected_issues.len(), count).into_vec();
+            picked.sort_unstable();
+            *selected_issues = picked.into_iter().map(|i| selected_issues[i]).collect();
+        }
+    }
+}
+
+// None when going back to the previous prompt
+fn select_members<'a>(
+    config: &Config,
+    members: &'a [GitlabProjectMember],
+    interactive: bool,
+) -> Option<Vec<&'a GitlabProjectMember>> {
+    if let Some(config_members) = config_strings(config, "members") {
+        let selected_members: Vec<&GitlabProjectMember> = members
+            .iter()
+            .filter(|member| config_members.contains(&(member.username)))
+            .collect();
+        Some(selected_members)
+    } else if !interactive {
+        Some(members.iter().collect())
+    } else {
+        let selection = MultiSelect::with_theme(&*theme())
+            .with_prompt("Select all the members you want to asign the issues to (esc to go back):")
+            .items(members)
+            .interact_opt()
+            .unwrap()?;
+        Some(selection.into_iter().map(|i| &members[i]).collect())
+    }
+}
+
+// what the issues were selected by, saved in the profile
+#[derive(Debug, Default)]
+struct ChosenSelection {
+    selection: Option<IssueSelectionType>,
+    milestones: Option<Vec<String>>,
+    iteration: Option<Vec<String>>,
+    labels: Option<Vec<String>>,
+    search: Option<String>,
+}
+
+// outcome of the issue selection step of a roll
+enum IssueSelection<'a> {
+    // back to the project prompt
+    Back,
+    Selected {
+        issues: Vec<&'a GitlabIssue>,
+        chosen: ChosenSelection,
+        // going back from the members returns to the issue prompts, when there were any
+        prompted: bool,
+    },
+}
+
+// can_go_back is set when the project was picked from the list, so it can be picked again
+#[allow(clippy::too_many_arguments)]
+fn select_issues<'a>(
+    args: &RollArgs,
+    config: &Config,
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    scopes: &[Scope],
+    issues: &'a [GitlabIssue],
+    milestones: &Option<Vec<String>>,
+    milestones_contains: &Option<Vec<String>>,
+    interactive: bool,
+    can_go_back: bool,
+) -> IssueSelection<'a> {
+    let mut chosen = ChosenSelection::default();
+    let mut issues_prompted = false;
+    let config_issues = config.get_array("issues");
+    let selected_issues = if let Ok(config_issues) = config_issues {
+        let config_issues: Vec<i64> = config_issues
+            .into_iter()
+            .map(|val| val.into_int().expect("provided issue id is not an int"))
+            .collect();
+        let unknown: Vec<String> = config_issues
+            .iter()
+            .filter(|&&iid| !issues.iter().any(|issue| issue.iid as i64 == iid))
+            .map(|iid| format!("#{}", iid))
+            .collect();
+        if !unknown.is_empty() {
+            fail(
+                EXIT_ERROR,
+                format!(
+                    "the following issues cannot be found, or were filtered out: {}",
+                    unknown.join(", ")
+                ),
+            );
+        }
+        let selected_issues: Vec<&GitlabIssue> = issues
+            .iter()
+            .filter(|issue| config_issues.contains(&(issue.iid as i64)))
+            .collect();
+        selected_issues
+    } else if let Some(iterations) = config_strings(config, "iteration") {
+        let selected_issues: Vec<&GitlabIssue> = issues
+            .iter()
+            .filter(|issue| {
+                issue.iteration.as_ref().is_some_and(|issue_iteration| {
+                    iterations.iter().any(|iteration| {
+                        if iteration == "current" {
+                            issue_iteration.is_current()
+                        } else {
+                            issue_iteration.id.to_string() == *iteration
+                        }
+                    })
+                })
+            })
+            .collect();
+        chosen.selection = Some(IssueSelectionType::Iteration);
+        chosen.iteration = Some(iterations);
+        selected_issues
+    } else if let Some(labels) = config_strings(config, "labels") {
+        let selected_issues: Vec<&GitlabIssue> = issues
+            .iter()
+            .filter(|issue| issue.labels.iter().any(|l| labels.contains(l)))
+            .collect();
+        chosen.selection = Some(IssueSelectionType::Label);
+        chosen.labels = Some(labels);
+        selected_issues
+    } else if let Ok(term) = config.get_string("search") {
+        // only the matching issues were fetched
+        chosen.selection = Some(IssueSelectionType::Search);
+        chosen.search = Some(term);
+        issues.iter().collect()
+    } else if !interactive
+        && milestones.is_none()
+        && milestones_contains.is_none()
+        && !args.only_milestone_current
+    {
+        issues.iter().collect()
+    } else {
+        let selection_types = [
+            IssueSelectionType::Milestone,
+            IssueSelectionType::Iteration,
+            IssueSelectionType::Range,
+            IssueSelectionType::Manual,
+            IssueSelectionType::Label,
+            IssueSelectionType::Search,
+        ];
+
+        let milestones_from_cli = milestones.is_some() || milestones_contains.is_some();
+        let current_milestone = if args.only_milestone_current {
+            current_milestone(client, gitlab_domain, token, scopes)
+        } else {
+            None
+        };
+        // a profile can keep the way the issues were selected without the values, for the ranges
+        // and the manual selection
+        let preselected = if milestones_from_cli || args.only_milestone_current {
+            Some(IssueSelectionType::Milestone)
+        } else {
+            config_enum(config, "selection")
+        };
+        let mut asked = false;
+        loop {
+            // without the mode prompt, going back from the issue prompts goes back to the project
+            if asked && preselected.is_some() {
+                if can_go_back {
+                    return IssueSelection::Back;
+                }
+                nothing_to_go_back();
+            }
+            asked = true;
+            let selection_type = if let Some(selection_type) = preselected {
+                // the milestone prompt is skipped when they are known
+                if !matches!(selection_type, IssueSelectionType::Milestone) {
+                    issues_prompted = true;
+                }
+                selection_type
+            } else {
+                issues_prompted = true;
+                let mut items: Vec<String> =
+                    selection_types.iter().map(|t| t.to_string()).collect();
+                if can_go_back {
+                    items.push("Go back".to_string());
+                }
+                let selection_type_res = Select::with_theme(&*theme())
+                    .with_prompt("Select the way you want to select the issues:")
+                    .items(&items)
+                    .interact()
+                    .unwrap();
+                if selection_type_res == selection_types.len() {
+                    return IssueSelection::Back;
+                }
+
+                selection_types[selection_type_res]
+            };
+
+            let selected_issues: Vec<&GitlabIssue> = match selection_type {
+                IssueSelectionType::Manual => {
+                    let Some(selection) = MultiSelect::with_theme(&*theme())
+                        .with_prompt(
+                            "Select all the issues that you want to use (esc to go back): ",
+                        )
+                        .items(issues)
+                        .interact_opt()
+                        .unwrap()
+                    else {
+                        continue;
+                    };
+
+                    let selected_issues: Vec<&GitlabIssue> =
+                        selection.into_iter().map(|i| &issues[i]).collect();
+
+                    selected_issues
+                }
+                IssueSelectionType::Milestone => {
+                    let mut issue_milestones: Vec<&GitlabMilestone> = Vec::new();
+                    issues.iter().for_each(|issue| {
+                        if let Some(milestone) = &issue.milestone {
+                            if !issue_milestones.contains(&milestone) {
+                                issue_milestones.push(milestone);
+                            }
+                        }
+                    });
+
+                    if issue_milestones.is_empty() {
+                        fail(
+                            EXIT_ERROR,
+                            "no milestone with opened issue, aborting".to_string(),
+                        );
+                    }
+
+                    let current_milestone = current_milestone.as_ref().and_then(|current| {
+                        let found = issue_milestones
+                            .iter()
+                            .copied()
+                            .find(|milestone| *milestone == current);
+                        if found.is_none() {
+                            eprintln!(
+                                "Note: the current milestone {} has no issue to assign",
+                                current
+                            );
+                        }
+                        found
+                    });
+
+                    let selected_milestones: Vec<&GitlabMilestone> = if let Some(current) =
+                        current_milestone
+                    {
+                        status!("Using the current milestone {}", current);
+                        vec![current]
+                    } else if milestones_from_cli {
+                        select_milestones_by_title(
+                            &issue_milestones,
+                            milestones.as_deref().unwrap_or_default(),
+                            milestones_contains.as_deref().unwrap_or_default(),
+                        )
+                    } else {
+                        if !interactive {
+                            fail(
+                                EXIT_ERROR,
+                                "no current milestone to use and the milestone cannot be asked"
+                                    .to_string(),
+                            );
+                        }
+                        // milestones of past sprints are usually closed but can still have issues
+                        if !args.include_closed_milestones {
+                            issue_milestones.retain(|milestone| milestone.state == "active");
+                            if issue_milestones.is_empty() {
+                                fail(EXIT_ERROR, "no active milestone with opened issue, use --include-closed-milestones to list the closed ones".to_string());
+                            }
+                        }
+
+                        issues_prompted = true;
+                        let Some(selection) = MultiSelect::with_theme(&*theme())
+                            .with_prompt(
+                                "Select all the milestones that you want to use (esc to go back): ",
+                            )
+                            .items(&issue_milestones)
+                            .interact_opt()
+                            .unwrap()
+                        else {
+                            continue;
+                        };
+
+                        selection.into_iter().map(|i| issue_milestones[i]).collect()
+                    };
+                    chosen.milestones = Some(
+                        selected_milestones
+                            .iter()
+                            .map(|milestone| milestone.title.clone())
+                            .collect(),
+                    );
+
+                    let selected_issues: Vec<&GitlabIssue> = issues
+                        .iter()
+                        .filter(|issue| {
+                            issue.milestone.is_some()
+                                && selected_milestones.contains(&issue.milestone.as_ref().unwrap())
+                        })
+                        .collect();
+
+                    selected_issues
+                }
+                IssueSelectionType::Range => {
+                    let Some(range_start) = issue_id_select(
+                        issues,
+                        "Enter the number (#) of the first issue (empty to go back):",
+                        None,
+                    ) else {
+                        continue;
+                    };
+                    let Some(range_end) = issue_id_select(
+                        issues,
+                        "Enter the number (#) of the last issue (empty to go back):",
+                        Some(range_start),
+                    ) else {
+                        continue;
+                    };
+
+                    let selected_issues: Vec<&GitlabIssue> = issues
+                        .iter()
+                        .filter(|issue| issue.iid >= range_start && issue.iid <= range_end)
+                        .collect();
+                    selected_issues
+                }
+                IssueSelectionType::Iteration => {
+                    let mut iterations: Vec<&GitlabIteration> = Vec::new();
+                    issues.iter().for_each(|issue| {
+                        if let Some(iteration) = &issue.iteration {
+                            if !iterations.contains(&iteration) {
+                                iterations.push(iteration);
+                            }
+                        }
+                    });
+
+                    if iterations.is_empty() {
+                        fail(
+                            EXIT_ERROR,
+                            "no iteration with opened issue, aborting".to_string(),
+                        );
+                    }
+                    iterations.sort_by_key(|iteration| iteration.start_date);
+
+                    let Some(selection) = MultiSelect::with_theme(&*theme())
+                        .with_prompt(
+                            "Select all the iterations that you want to use (esc to go back): ",
+                        )
+                        .items(&iterations)
+                        .interact_opt()
+                        .unwrap()
+                    else {
+                        continue;
+                    };
+                    let selected_iterations: Vec<&GitlabIteration> =
+                        selection.into_iter().map(|i| iterations[i]).collect();
+                    chosen.iteration = Some(
+                        selected_iterations
+                            .iter()
+                            .map(|iteration| iteration.id.to_string())
+                            .collect(),
+                    );
+
+                    let selected_issues: Vec<&GitlabIssue> = issues
+                        .iter()
+                        .filter(|issue| {
+                            issue
+                                .iteration
+                                .as_ref()
+                                .is_some_and(|iteration| selected_iterations.contains(&iteration))
+                        })
+                        .collect();
+                    selected_issues
+                }
+                IssueSelectionType::Label => {
+                    let mut labels: HashSet<String> = HashSet::new();
+                    issues.iter().for_each(|issue| {
+                        labels.extend(issue.labels.clone());
+                    });
+
+                    if labels.is_empty() {
+                        fail(
+                            EXIT_ERROR,
+                            "no label with opened issue, aborting".to_string(),
+                        );
+                    }
+
+                    let labels: Vec<&String> = labels.iter().collect();
+                    let Some(selection) = MultiSelect::with_theme(&*theme())
+                        .with_prompt(
+                            "Select all the labels that you want to use (esc to go back): ",
+                        )
+                        .items(&labels)
+                        .interact_opt()
+                        .unwrap()
+                    else {
+                        continue;
+                    };
+
+                    let selected_labels: Vec<String> =
+                        selection.into_iter().map(|i| labels[i].clone()).collect();
+
+                    let selected_issues: Vec<&GitlabIssue> = issues
+                        .iter()
+                        .filter(|issue| issue.labels.iter().any(|l| selected_labels.contains(l)))
+                        .collect();
+                    chosen.labels = Some(selected_labels);
+
+                    selected_issues
+                }
+                IssueSelectionType::Search => {
+                    let term: String = Input::with_theme(&*theme())
+                        .with_prompt("Search the issues for (empty to go back):")
+                        .allow_empty(true)
+                        .interact_text()
+                        .unwrap();
+                    if term.trim().is_empty() {
+                        continue;
+                    }
+                    let state = config_enum(config, "state").unwrap_or(IssueState::Opened);
+                    let selected_issues =
+                        search_issues(client, gitlab_domain, token, scopes, state, &term, issues);
+                    chosen.search = Some(term);
+                    selected_issues
+                }
+            };
+            chosen.selection = Some(selection_type);
+            break selected_issues;
+        }
+    };
+
+    IssueSelection::Selected {
+        issues: selected_issues,
+        chosen,
+        prompted: issues_prompted,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn roll(
+    args: &RollArgs,
+    config: &Config,
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    url: &str,
+    history_file: &str,
+    config_file: &str,
+    config_format: ConfigFormat,
+) {
+    // these can come from the args or from a profile
+    let milestones = config_strings(config, "milestones");
+    let milestones_contains = config_strings(config, "milestones_contains");
+    let mode = config_enum(config, "mode").unwrap_or(AssignMode::Random);
+    let types: Option<Vec<IssueType>> = config_strings(config, "types").map(|types| {
+        types
+            .iter()
+            .map(|t| {
+                IssueType::from_str(t, true).unwrap_or_else(|_| {
+                    fail(EXIT_CONFIG, format!("unknown issue type \"{}\"", t));
+                })
+            })
+            .collect()
+    });
+
+    let json_output = args.dry_run && matches!(args.output_format, OutputFormat::Json);
+    // the json plan is meant to be piped, so nothing can be asked
+    let unattended = args.yes || args.force;
+    let interactive = !json_output && !unattended;
+    MACHINE_OUTPUT.store(json_output, Ordering::Relaxed);
+
+    let mut rng = match args.seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    };
+
+    // the prompts can go back to the previous one, the issues and members are only fetched again
+    // when another project is picked
+    let mut pick_project = false;
+    let mut scopes: Vec<Scope> = Vec::new();
+    let mut issues: Vec<GitlabIssue> = Vec::new();
+    let mut members: Vec<GitlabProjectMember> = Vec::new();
+    let (selected_issues, selected_members, chosen) = 'project: loop {
+        let (scope, project_prompted) = select_scope(
+            config,
+            client,
+            gitlab_domain,
+            token,
+            url,
+            interactive,
+            pick_project,
+        );
+        if scopes.first().map(|scope| scope.path()) != Some(scope.path()) {
+            scopes = vec![scope];
+            add_other_projects(config, client, gitlab_domain, token, &mut scopes);
+
+            issues = fetch_roll_issues(args, config, client, gitlab_domain, token, &scopes);
+            members = fetch_roll_members(args, config, client, gitlab_domain, token, &scopes);
+        }
+
+        'issues: loop {
+            let (mut selected_issues, chosen, issues_prompted) = match select_issues(
+                args,
+                config,
+                client,
+                gitlab_domain,
+                token,
+                &scopes,
+                &issues,
+                &milestones,
+                &milestones_contains,
+                interactive,
+                project_prompted,
+            ) {
+                IssueSelection::Back => {
+                    pick_project = true;
+                    continue 'project;
+                }
+                IssueSelection::Selected {
+                    issues,
+                    chosen,
+                    prompted,
+                } => (issues, chosen, prompted),
+            };
+
+            narrow_issues(args, &types, &mut selected_issues, &mut rng);
+
+            if selected_issues.is_empty() {
+                status!("No issue selected, nothing to assign");
+                process::exit(0);
+            }
+
+            let selected_members = match select_members(config, &members, interactive) {
+                Some(selected_members) => selected_members,
+                None if issues_prompted => continue 'issues,
+                None if project_prompted => {
+                    pick_project = true;
+                    continue 'project;
+                }
+                None => nothing_to_go_back(),
+            };
+
+            if selected_members.is_empty() {
+                status!("No member selected, nothing to assign");
+                process::exit(0);
+            }
+
+            break 'project (selected_issues, selected_members, chosen);
+        }
+    };
+
+    // the priorities are balanced the same way as the weights
+    let spread_by_age = config.get_bool("spread_by_age").unwrap_or(false);
+    let by_priority = config.get_bool("by_priority").unwrap_or(false);
+    let balance_by = config_enum(config, "balance_by").unwrap_or(if by_priority {
+        BalanceBy::Weight
+    } else {
+        BalanceBy::Count
+    });
+    if by_priority && balance_by == BalanceBy::Count {
+        fail(
+            EXIT_ERROR,
+            "--by-priority cannot be used with --balance-by count".to_string(),
+        );
+    }
+    let balanced = balance_by != BalanceBy::Count;
+
+    if let Some(name) = &args.save_profile {
+        let profile = Profile {
+            members: selected_members
+                .iter()
+                .map(|member| member.username.clone())
+                .collect(),
+            selection: chosen
+                .selection
+                .and_then(|selection| selection.to_possible_value())
+                .map(|selection| selection.get_name().to_string()),
+            milestones: chosen.milestones.or(milestones),
+            milestones_contains,
+            iteration: chosen.iteration,
+            labels: chosen.labels,
+            search: chosen.search,
+            state: config.get_string("state").ok(),
+            unassigned_only: config
+                .get_bool("unassigned_only")
+                .unwrap_or(false)
+                .then_some(true),
+            mode: mode.to_string(),
+            balance_by: balanced.then(|| balance_by.to_string()),
+            spread_by_age: spread_by_age.then_some(true),
+            by_priority: by_priority.then_some(true),
+            types: types.map(|types| types.iter().map(|t| t.to_string()).collect()),
+        };
+        match save_profile(config_file, config_format, name, &profile) {
+            Ok(()) => status!("Profile {} saved to {}", name, config_file),
+            Err(err) => eprintln!("Warning: failed to save the profile: {}", err),
+        }
+    }
+
+    let assignees_per_issue = args.assignees_per_issue;
+    if assignees_per_issue == 0 {
+        fail(
+            EXIT_ERROR,
+            "--assignees-per-issue must be at least 1".to_string(),
+        );
+    }
+    if assignees_per_issue > 1 && LEGACY_ASSIGN.load(Ordering::Relaxed) {
+        fail(
+            EXIT_ERROR,
+            "only one member can be assigned to each issue with --legacy-assign".to_string(),
+        );
+    }
+
+    let limits = MemberLimits {
+        min: args.min_per_member.unwrap_or(0),
+        max: args.max_per_member,
+    };
+    let weights: Vec<i32> = if by_priority {
+        let priorities = label_priorities(config);
+        selected_issues
+            .iter()
+            .map(|issue| {
+                issue
+                    .labels
+                    .iter()
+                    .filter_map(|label| priorities.get(label).copied())
+                    .max()
+                    .unwrap_or(1)
+            })
+            .collect()
+    } else {
+        selected_issues
+            .iter()
+            .map(|issue| issue.weight_or_default())
+            .collect()
+    };
+    let RollDraw {
+        members: mut selected_members,
+        mut current_assignees,
+        assignements,
+    } = draw_roll(
+        args,
+        mode,
+        balance_by,
+        spread_by_age,
+        &weights,
+        &selected_issues,
+        selected_members,
+        limits,
+        &mut rng,
+    );
+    // the first draw, then the issues of the others kept as they were after removing a member
+    let mut redistributed = Some(assignements);
+    let assignements = loop {
+        let mut assignements = match redistributed.take() {
+            Some(assignements) => assignements,
+            None => draw(
+                args,
+                mode,
+                balance_by,
+                spread_by_age,
+                &weights,
+                &selected_issues,
+                selected_members.len(),
+                limits,
+                &current_assignees,
+                &mut rng,
+            ),
+        };
+
+        if json_output {
+            print_plan_json(&scopes, &selected_issues, &selected_members, &assignements);
+            process::exit(0);
+        }
+
+        // each pairing gets confirmed on its own below
+        if args.interactive_confirm {
+            break assignements;
+        }
+
+        // the animation rewrites lines in place, which only works on a terminal
+        let animate = !args.no_animation && io::stdout().is_terminal();
+        println!();
+        if let PreviewFormat::Table = args.format {
+            print_preview_table(&selected_issues, &selected_members, &assignements);
+        } else {
+            for (i, issue) in selected_issues.iter().enumerate() {
+                if args.suspense && animate {
+                    suspense(issue, args.reveal_delay);
+                }
+                println!("#{}: {}", issue.iid, issue.title);
+                for &member in &assignements[i] {
+                    if !animate {
+                        println!("\t{}", selected_members[member]);
+                    } else {
+                        // not the seeded rng, so the animation does not change the next draws
+                        reveal_member(&selected_members, member, &mut rand::thread_rng());
+                    }
+                }
+                println!(
+                    "\t{}",
+                    assignees_change(issue, &selected_members, &assignements[i])
+                );
+            }
+        }
+
+        print_summary(
+            selected_issues.len(),
+            &selected_members,
+            &assignements,
+            balanced.then_some((
+                weights.as_slice(),
+                if by_priority { "priority" } else { "weight" },
+            )),
+        );
+
+        if args.dry_run {
+            println!();
+            println!("Dry run, nothing was assigned");
+            process::exit(0);
+        }
+
+        if unattended {
+            break assignements;
+        }
+
+        let mut actions = vec![PreviewAction::Confirm, PreviewAction::Reroll];
+        // each issue still needs enough members left once one is removed, within the member limits
+        if selected_members.len() > assignees_per_issue
+            && limits
+                .check(
+                    selected_issues.len(),
+                    selected_members.len() - 1,
+                    assignees_per_issue,
+                )
+                .is_ok()
+        {
+            actions.push(PreviewAction::RemoveMember);
+        }
+        actions.push(PreviewAction::Cancel);
+        let action = Select::with_theme(&*theme())
+            .with_prompt("Do you want to confirm this assignment ?")
+            .items(&actions)
+            .default(0)
+            .interact()
+            .unwrap();
+
+        match actions[action] {
+            PreviewAction::Confirm => break assignements,
+            PreviewAction::Reroll => continue,
+            PreviewAction::RemoveMember => {
+                let member = Select::with_theme(&*theme())
+                    .with_prompt("Select the member to remove:")
+                    .items(&selected_members)
+                    .interact()
+                    .unwrap();
+                let load_weights = if balanced {
+                    weights.clone()
+                } else {
+                    vec![1; selected_issues.len()]
+                };
+                remove_member(
+                    &mut assignements,
+                    member,
+                    selected_members.len(),
+                    &load_weights,
+                    &mut rng,
+                );
+                selected_members.remove(member);
+                for current in current_assignees.iter_mut() {
+                    current.retain(|&m| m != member);
+                    for m in current.iter_mut() {
+                        if *m > member {
+                            *m -= 1;
+                        }
+                    }
+                }
+                // handing out the issues of the removed member can push the others past the limits,
+                // they are drawn again in that case
+                let mut counts = vec![0; selected_members.len()];
+                assignements.iter().flatten().for_each(|&m| counts[m] += 1);
+                if counts
+                    .iter()
+                    .all(|&count| count >= limits.min && limits.max.is_none_or(|max| count <= max))
+                {
+                    redistributed = Some(assignements);
+                } else {
+                    eprintln!("Note: the issues cannot be handed out to the others within the member limits, drawing them again");
+                }
+                continue;
+            }
+            PreviewAction::Cancel => {
+                println!("Exiting");
+                process::exit(EXIT_CANCELLED);
+            }
+        }
+    };
+
+    // accepting the plan with --yes does not allow writing, a scripting mistake could reassign a whole board
+    if args.yes && !args.commit && !args.force {
+        if !io::stdin().is_terminal() {
+            fail(
+                EXIT_CANCELLED,
+                "--yes only accepts the plan, add --commit (or use --force) to assign the issues without being asked".to_string(),
+            );
+        }
+        let confirmed = Confirm::with_theme(&*theme())
+            .with_prompt(format!("Assign the {} issues now ?", selected_issues.len()))
+            .default(false)
+            .interact()
+            .unwrap();
+        if !confirmed {
+            println!("Exiting");
+            process::exit(EXIT_CANCELLED);
+        }
+    }
+
+    let (selected_issues, assignements) = if args.interactive_confirm {
+        let mut assignements = assignements;
+        let kept = confirm_each_assignment(&selected_issues, &selected_members, &mut assignements);
+        if kept.is_empty() {
+            println!("Every issue was skipped, nothing to assign");
+            process::exit(EXIT_CANCELLED);
+        }
+        let issues: Vec<&GitlabIssue> = kept.iter().map(|&i| selected_issues[i]).collect();
+        let assignements: Vec<Vec<usize>> = kept.iter().map(|&i| assignements[i].clone()).collect();
+        (issues, assignements)
+    } else {
+        (selected_issues, assignements)
+    };
+
+    let comment_template = config
+        .get_string("comment_template")
+        .unwrap_or(DEFAULT_COMMENT_TEMPLATE.to_string());
+
+    let comment_template = args.comment.then_some(comment_template.as_str());
+
+    // read before assigning anything, a corrupt history stops the roll while nothing is lost
+    let mut history = read_history(history_file);
+
+    // the issues are handed out to a bounded number of worker threads
+    let next_issue = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    // the progress line is rewritten in place, which only works on a terminal
+    let show_progress = !args.no_animation && io::stdout().is_terminal();
+    let mut results: Vec<(usize, Result<(), String>)> = Vec::new();
+    // ctrl-c stops handing out issues, so the ones already assigned still end up in the history
+    if let Err(err) = ctrlc::set_handler(|| {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        eprintln!("Interrupted, waiting for the assignments in progress...");
+    }) {
+        warn!("failed to set the ctrl-c handler: {}", err);
+    }
+    thread::scope(|scope| {
+        for _ in 0..args.concurrency.max(1).min(selected_issues.len()) {
+            let sender = sender.clone();
+            let next_issue = &next_issue;
+            let selected_issues = &selected_issues;
+            let selected_members = &selected_members;
+            let assignements = &assignements;
+            scope.spawn(move || loop {
+                if INTERRUPTED.load(Ordering::Relaxed) {
+                    break;
+                }
+                let i = next_issue.fetch_add(1, Ordering::Relaxed);
+                if i >= selected_issues.len() {
+                    break;
+                }
+                let assignees: Vec<&GitlabProjectMember> = assignements[i]
+                    .iter()
+                    .map(|&member| selected_members[member])
+                    .collect();
+                let result = assign_issue(
+                    client,
+                    gitlab_domain,
+                    token,
+                    selected_issues[i],
+                    &assignees,
+                    comment_template,
+                );
+                sender.send((i, result)).unwrap();
+            });
+        }
+        drop(sender);
+
+        for (i, result) in receiver {
+            if show_progress {
+                if !results.is_empty() {
+                    print!("{}{}", CursorUp(1), EraseLine);
+                }
+                println!(
+                    "[{}/{}] #{} {}",
+                    results.len() + 1,
+                    selected_issues.len(),
+                    selected_issues[i].iid,
+                    if result.is_ok() { "assigned" } else { "failed" }
+                );
+            }
+            results.push((i, result));
+        }
+        if show_progress && !results.is_empty() {
+            print!("{}{}", CursorUp(1), EraseLine);
+        }
+    });
+    results.sort_by_key(|(i, _)| *i);
+    let (succeeded, failed): (Vec<_>, Vec<_>) =
+        results.into_iter().partition(|(_, result)| result.is_ok());
+
+    // only the issues that were actually assigned can be undone
+    if !succeeded.is_empty() {
+        let entry = HistoryEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system time is before the unix epoch")
+                .as_secs(),
+            issues: succeeded
+                .iter()
+                .map(|&(i, _)| HistoryIssue {
+                    project_id: selected_issues[i].project_id,
+                    iid: selected_issues[i].iid,
+                    previous_assignee_ids: selected_issues[i]
+                        .assignees
+                        .iter()
+                        .map(|member| member.id)
+                        .collect(),
+                })
+                .collect(),
+        };
+        // the issues are already assigned, so the previous assignees are printed instead of giving up
+        let previous = serde_json::to_string(&entry).expect("failed to serialize history");
+        history.push(entry);
+        if let Err(err) = write_history(history_file, &history) {
+            eprintln!(
+                "Warning: failed to write the history file \"{}\": {}, undo will not work for this roll. The previous assignees were:\n{}",
+                history_file, err, previous
+            );
+        }
+    }
+
+    if let (Ok(webhook), false) = (config.get_string("webhook"), succeeded.is_empty()) {
+        let issues: Vec<&GitlabIssue> =
+            succeeded.iter().map(|&(i, _)| selected_issues[i]).collect();
+        let issue_assignements: Vec<Vec<usize>> = succeeded
+            .iter()
+            .map(|&(i, _)| assignements[i].clone())
+            .collect();
+        let plan = planned_roll(&scopes, &issues, &selected_members, &issue_assignements);
+        post_webhook(client, &webhook, &plan);
+    }
+
+    // the Display of the issues lists their previous assignees, the new ones are listed instead
+    let assigned = |i: usize| {
+        let usernames: Vec<&str> = assignements[i]
+            .iter()
+            .map(|&member| selected_members[member].username.as_str())
+            .collect();
+        format!(
+            "#{}: {} → {}",
+            selected_issues[i].iid,
+            selected_issues[i].title,
+            usernames.join(", ")
+        )
+    };
+
+    if INTERRUPTED.load(Ordering::Relaxed) {
+        println!(
+            "Interrupted, {} of the {} issues were assigned (gitlab-roulette undo reverts them):",
+            succeeded.len(),
+            selected_issues.len()
+        );
+        for (i, _) in &succeeded {
+            println!("\t{}", assigned(*i));
+        }
+        if !failed.is_empty() {
+            eprintln!("{} issues failed to be assigned", failed.len());
+        }
+        process::exit(EXIT_CANCELLED);
+    }
+
+    if let SummaryFormat::Markdown = args.summary_format {
+        println!();
+        for (i, _) in &succeeded {
+            let issue = selected_issues[*i];
+            let mentions: Vec<String> = assignements[*i]
+                .iter()
+                .map(|&member| format!("@{}", selected_members[member].username))
+                .collect();
+            println!(
+                "- [#{} {}]({}) → {}",
+                issue.iid,
+                escape_markdown(&issue.title),
+                issue.web_url,
+                mentions.join(", ")
+            );
+        }
+        println!();
+    }
+
+    if failed.is_empty() {
+        println!("issues assigned !");
+        return;
+    }
+
+    println!(
+        "Assigned {} of the {} issues:",
+        succeeded.len(),
+        selected_issues.len()
+    );
+    for (i, _) in &succeeded {
+        println!("\t{}", assigned(*i));
+    }
+    let mut message = format!("Failed to assign {} issues:", failed.len());
+    for (i, result) in failed {
+        let issue = selected_issues[i];
+        message.push_str(&format!("\n\t#{}: {}", issue.iid, issue.title));
+        if let Err(err) = result {
+            message.push_str(&format!("\n\t\t{}", err));
+        }
+    }
+    // nothing was assigned at all when every issue failed
+    fail(
+        if succeeded.is_empty() {
+            EXIT_ERROR
+        } else {
+            EXIT_PARTIAL_FAILURE
+        },
+        message,
+    );
+}
+
+// only access tokens can be inspected, and only since gitlab 15.5
+fn fetch_token_info(
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+) -> Option<GitlabAccessToken> {
+    let res = send(add_auth(
+        client.get(format!(
+            "{}/api/v4/personal_access_tokens/self",
+            gitlab_domain
+        )),
+        token,
+    ));
+    let res = check_status(res, "get the token").ok()?;
+    Some(parse_body(res, "get the token"))
+}
+
+// fails before anything is selected instead of after every assignment failed
+// a dry run never writes anything, so only the scopes of the token are checked
+fn check_write(
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    issue: &GitlabIssue,
+    dry_run: bool,
+) {
+    if let Some(token_info) = fetch_token_info(client, gitlab_domain, token) {
+        if !token_info.scopes.iter().any(|scope| scope == "api") {
+            fail(
+                EXIT_CONFIG,
+                "your token lacks the api scope, it cannot assign issues".to_string(),
+            );
+        }
+    }
+    if dry_run {
+        status!("The token has the api scope, its role is not checked during a dry run");
+        return;
+    }
+
+    // the scopes do not tell if the role is high enough, so an issue gets its own assignees back
+    let current_ids: Vec<i32> = issue.assignees.iter().map(|member| member.id).collect();
+    let res = send(add_auth(
+        client
+            .put(format!(
+                "{}/api/v4/projects/{}/issues/{}",
+                gitlab_domain, issue.project_id, issue.iid
+            ))
+            .query(&assignee_ids_query(&current_ids)),
+        token,
+    ));
+    if let Err(err) = check_status(res, "update an issue") {
+        fail(
+            EXIT_CONFIG,
+            format!("your token cannot assign issues: {}", err),
+        );
+    }
+    status!("The token can assign issues");
+}
+
+fn whoami(client: &Client, gitlab_domain: &str, token: &str, user: &GitlabUser) {
+    println!("Logged in as {} ({})", user.username, user.name);
+    println!("User id: {}", user.id);
+
+    let Some(token_info) = fetch_token_info(client, gitlab_domain, token) else {
+        println!("Token scopes: unknown");
+        return;
+    };
+    println!("Token scopes: {}", token_info.scopes.join(", "));
+    if let Some(expires_at) = token_info.expires_at {
+        println!("Token expires on: {}", expires_at);
+    }
+    // assigning issues and posting comments needs write access to the api
+    if !token_info.scopes.iter().any(|scope| scope == "api") {
+        eprintln!("Warning: the token does not have the api scope, it cannot assign issues");
+    }
+}
+
+fn list_members(config: &Config, client: &Client, gitlab_domain: &str, token: &str, url: &str) {
+    let (scope, _) = select_scope(config, client, gitlab_domain, token, url, true, false);
+
+    let members = fetch_members(client, gitlab_domain, token, &scope.path());
+
+    let issues: Vec<GitlabIssue> = page_fetch(
+        client,
+        format!(
+            "{}/api/v4/{}/issues?state=opened",
+            gitlab_domain,
+            scope.path()
+        ),
+        token,
+    );
+
+    let mut loads: Vec<(&GitlabProjectMember, usize)> = members
+        .iter()
+        .map(|member| {
+            let count = issues
+                .iter()
+                .filter(|issue| issue.assignees.iter().any(|a| a.id == member.id))
+                .count();
+            (member, count)
+        })
+        .collect();
+    loads.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+
+    let names: Vec<String> = loads.iter().map(|(member, _)| member.to_string()).collect();
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (name, (_, count)) in names.iter().zip(&loads) {
+        let issues = if *count == 1 { "issue" } else { "issues" };
+        println!("{:<width$}  {:>3} open {}", name, count, issues);
+    }
+}
+
+// "-" is replaced by the numbers read from stdin, separated by commas or whitespace
+fn parse_issue_ids(values: &[String]) -> Vec<i64> {
+    let mut ids = Vec::new();
+    for value in values {
+        let value = if value == "-" {
+            io::read_to_string(io::stdin()).unwrap_or_else(|err| {
+                fail(
+                    EXIT_ERROR,
+                    format!("failed to read the issues from stdin: {}", err),
+                );
+            })
+        } else {
+            value.clone()
+        };
+        for id in value.split(|c: char| c == ',' || c.is_whitespace()) {
+            let id = id.trim().trim_start_matches('#');
+            if id.is_empty() {
+                continue;
+            }
+            match id.parse::<i64>() {
+                Ok(id) => ids.push(id),
+                Err(_) => {
+                    fail(EXIT_CONFIG, format!("\"{}\" is not an issue number", id));
+                }
+            }
+        }
+    }
+    ids
+}
+
+// multiplier of each label, from the priorities table of the config
+fn label_priorities(config: &Config) -> HashMap<String, i32> {
+    let Ok(priorities) = config.get_table("priorities") else {
+        return HashMap::new();
+    };
+    priorities
+        .into_iter()
+        .map(|(label, priority)| {
+            let priority = priority.into_int().unwrap_or_else(|_| {
+                fail(
+                    EXIT_CONFIG,
+                    format!("the priority of the label \"{}\" is not a number", label),
+                )
+            });
+            (label, priority as i32)
+        })
+        .collect()
 }
 
-impl PartialEq for GitlabMilestone {
-    fn eq(&self, other: &Self) -> bool {
-        return self.id == other.id;
-    }
+fn config_strings(config: &Config, key: &str) -> Option<Vec<String>> {
+    let values = config.get_array(key).ok()?;
+    Some(
+        values
+            .into_iter()
+            .map(|val| {
+                val.into_string().unwrap_or_else(|_| {
+                    fail(
+                        EXIT_CONFIG,
+                        format!("provided {} is not a list of strings", key),
+                    )
+                })
+            })
+            .collect(),
+    )
 }
 
-fn page_fetch<T>(client: &Client, url: String, token: &String) -> Vec<T>
-where
-    T: for<'de> Deserialize<'de>,
-{
-    let mut results = Vec::new();
-    let mut page = 1;
-    loop {
-        let res = client
-            .get(
-                Url::parse_with_params(
-                    &url,
-                    &[("per_page", "100"), ("page", page.to_string().as_str())],
-                )
-                .unwrap(),
-            )
-            .header("PRIVATE-TOKEN", token.clone())
-            .send()
-            .expect("failed to execute request");
+// the values of the args enums, when they come from the config or a profile
+fn config_enum<T: ValueEnum>(config: &Config, key: &str) -> Option<T> {
+    let value = config.get_string(key).ok()?;
+    Some(T::from_str(&value, true).unwrap_or_else(|_| {
+        let expected: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value())
+            .map(|variant| variant.get_name().to_string())
+            .collect();
+        fail(
+            EXIT_CONFIG,
+            format!(
+                "unknown {} \"{}\", expected one of {}",
+                key,
+                value,
+                expected.join(", ")
+            ),
+        );
+    }))
+}
 
-        if !res.status().is_success() {
-            eprintln!(
-                "Failed to get the issue list : {} ({})",
-                res.status().canonical_reason().unwrap(),
-                res.status().as_str()
+// the rest of the config file is kept as it is, comments included for toml
+fn save_profile(
+    config_file: &str,
+    config_format: ConfigFormat,
+    name: &str,
+    profile: &Profile,
+) -> Result<(), String> {
+    let content = if fs::exists(config_file).map_err(|err| err.to_string())? {
+        fs::read_to_string(config_file).map_err(|err| err.to_string())?
+    } else {
+        String::new()
+    };
+
+    let content = match config_format {
+        ConfigFormat::Toml => {
+            let mut document = content
+                .parse::<toml_edit::DocumentMut>()
+                .map_err(|err| err.to_string())?;
+            // an inline table is turned into a regular one so the other profiles are kept
+            match document.get("profiles") {
+                None => {
+                    let mut profiles = toml_edit::Table::new();
+                    profiles.set_implicit(true);
+                    document.insert("profiles", toml_edit::Item::Table(profiles));
+                }
+                Some(profiles) if profiles.is_table() => {}
+                Some(profiles) => {
+                    let Some(profiles) = profiles.as_inline_table() else {
+                        return Err("profiles is not a table".to_string());
+                    };
+                    let profiles = profiles.clone().into_table();
+                    document.insert("profiles", toml_edit::Item::Table(profiles));
+                }
+            }
+            let profile = toml_edit::ser::to_document(profile).map_err(|err| err.to_string())?;
+            document["profiles"][name] = toml_edit::Item::Table(profile.as_table().clone());
+            document.to_string()
+        }
+        ConfigFormat::Json => {
+            let mut document: serde_json::Value = if content.trim().is_empty() {
+                serde_json::json!({})
+            } else {
+                serde_json::from_str(&content).map_err(|err| err.to_string())?
+            };
+            let Some(document_object) = document.as_object_mut() else {
+                return Err("the config file is not a json object".to_string());
+            };
+            let profiles = document_object
+                .entry("profiles")
+                .or_insert_with(|| serde_json::json!({}));
+            let Some(profiles) = profiles.as_object_mut() else {
+                return Err("profiles is not a json object".to_string());
+            };
+            profiles.insert(
+                name.to_string(),
+                serde_json::to_value(profile).map_err(|err| err.to_string())?,
             );
-            process::exit(1);
+            serde_json::to_string_pretty(&document).map_err(|err| err.to_string())?
         }
-
-        let res = res.text().expect("failed to get response body");
-        let page_issues = serde_json::from_str::<Vec<T>>(&res).expect("failed to parse issues");
-
-        if page_issues.len() == 0 {
-            return results;
+        ConfigFormat::Yaml => {
+            return Err("profiles can only be saved in toml or json config files".to_string())
         }
-        results.extend(page_issues);
-        page += 1;
-    }
+    };
+    fs::write(config_file, content).map_err(|err| err.to_string())
 }
 
-fn main() -> Result<(), ConfigError> {
-    let cli = Cli::parse();
+fn read_history(path: &str) -> Vec<HistoryEntry> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(err) => fail(
+            EXIT_ERROR,
+            format!("failed to read the history file \"{}\": {}", path, err),
+        ),
+    };
+    serde_json::from_str(&content).unwrap_or_else(|err| {
+        fail(
+            EXIT_ERROR,
+            format!("the history file \"{}\" is not valid: {}", path, err),
+        )
+    })
+}
 
-    let config_file = cli.config_file.unwrap();
+fn write_history(path: &str, history: &[HistoryEntry]) -> io::Result<()> {
+    let content = serde_json::to_string_pretty(history).expect("failed to serialize history");
+    fs::write(path, content)
+}
 
-    let mut builder = Config::builder();
-    if fs::exists(&config_file).expect("failed to check for config file") {
-        builder = builder.add_source(File::new(&config_file, FileFormat::Toml));
+fn assignee_ids_query(ids: &[i32]) -> Vec<(&'static str, i32)> {
+    // gitlab unassigns everyone when given 0
+    if LEGACY_ASSIGN.load(Ordering::Relaxed) {
+        vec![("assignee_id", ids.first().copied().unwrap_or(0))]
+    } else if ids.is_empty() {
+        vec![("assignee_ids", 0)]
+    } else {
+        ids.iter().map(|&id| ("assignee_ids[]", id)).collect()
     }
-    //  .add_async_source(...)
-    builder = builder
-        .set_override_option("url", cli.url)?
-        .set_override_option("token", cli.token)?
-        .set_override_option("issues", cli.issues)?
-        .set_override_option("members", cli.members)?;
-
-    let config = builder.build()?;
+}
 
-    let url = config.get_string("url");
-    let token = config.get_string("token");
+fn undo(client: &Client, gitlab_domain: &str, token: &str, history_file: &str) {
+    let mut history = read_history(history_file);
+    let entry = match history.pop() {
+        Some(entry) => entry,
+        None => {
+            fail(
+                EXIT_ERROR,
+                format!("no roll to undo in \"{}\"", history_file),
+            );
+        }
+    };
 
-    if !url.is_ok() {
-        eprintln!("Please add a url to the config file or using the --url argument");
-        process::exit(1);
-    }
+    for issue in &entry.issues {
+        let assignee_ids = assignee_ids_query(&issue.previous_assignee_ids);
+        let res = send(add_auth(
+            client
+                .put(format!(
+                    "{}/api/v4/projects/{}/issues/{}",
+                    gitlab_domain, issue.project_id, issue.iid
+                ))
+                .query(&assignee_ids),
+            token,
+        ));
 
-    let url = url.unwrap();
-    let url_parse = Url::parse(&url);
-    if !url_parse.is_ok() {
-        eprintln!("the url \"{}\" is not valid", url);
-        process::exit(1);
+        check_response(res, "restore the assignees of an issue");
     }
-    let url_parse = url_parse.unwrap();
-
-    let gitlab_domain = format!(
-        "{}://{}",
-        url_parse.scheme().to_string(),
-        url_parse
-            .domain()
-            .expect("failed to extract the domain out of the url")
-    );
 
-    if !token.is_ok() {
-        eprintln!("Please add a token to the config file or using the --token argument");
-        process::exit(1);
+    if let Err(err) = write_history(history_file, &history) {
+        eprintln!(
+            "Warning: failed to write the history file \"{}\": {}, the roll is still listed in it",
+            history_file, err
+        );
     }
+    println!("restored the assignees of {} issues !", entry.issues.len());
+}
 
-    let token = token.unwrap();
-
-    let client = reqwest::blocking::Client::new();
-
-    let projects: Vec<GitlabProject> = page_fetch(
-        &client,
-        format!(
-            "{}/api/v4/projects?membership=true&simple=true",
-            gitlab_domain
-        ),
-        &token,
-    );
+// lets the user accept, change or skip each pairing, returns the indices of the issues to assign
+fn confirm_each_assignment(
+    issues: &[&GitlabIssue],
+    members: &[&GitlabProjectMember],
+    assignements: &mut [Vec<usize>],
+) -> Vec<usize> {
+    let actions = vec![
+        PairingAction::Accept,
+        PairingAction::Reassign,
+        PairingAction::Skip,
+    ];
 
-    // try to find the project using URL
-    let project = projects.iter().find(|p| p.web_url == url);
-    let project = if let Some(project) = project {
-        println!("Found project: {}", project.name);
-        project
-    } else {
-        let projects_names: Vec<String> = projects
-            .iter()
-            .map(|proj| proj.path_with_namespace.clone())
-            .collect();
+    let mut kept = Vec::new();
+    for (i, issue) in issues.iter().enumerate() {
+        println!();
+        println!("#{}: {}", issue.iid, issue.title);
+        for &member in &assignements[i] {
+            println!("\t{}", members[member]);
+        }
+        println!("\t{}", assignees_change(issue, members, &assignements[i]));
 
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select a project: ")
-            .items(&projects_names)
+        let action = Select::with_theme(&*theme())
+            .with_prompt("What do you want to do with this issue ?")
+            .items(&actions)
+            .default(0)
             .interact()
             .unwrap();
 
-        &projects[selection]
-    };
-
-    let issues: Vec<GitlabIssue> = page_fetch(
-        &client,
-        format!(
-            "{}/api/v4/projects/{}/issues?state=opened",
-            gitlab_domain, project.id
-        ),
-        &token,
-    );
+        match actions[action] {
+            PairingAction::Accept => kept.push(i),
+            PairingAction::Reassign => {
+                for slot in 0..assignements[i].len() {
+                    let member = Select::with_theme(&*theme())
+                        .with_prompt("Select the member to assign this issue to:")
+                        .items(members)
+                        .default(assignements[i][slot])
+                        .interact()
+                        .unwrap();
+                    assignements[i][slot] = member;
+                }
+                // the same member may have been picked for several slots
+                assignements[i].sort_unstable();
+                assignements[i].dedup();
+                kept.push(i);
+            }
+            PairingAction::Skip => {}
+        }
+    }
+    kept
+}
 
-    let members: Vec<GitlabProjectMember> = page_fetch(
-        &client,
-        format!("{}/api/v4/projects/{}/members", gitlab_domain, project.id),
-        &token,
-    );
+fn assign_issue(
+    client: &Client,
+    gitlab_domain: &str,
+    token: &str,
+    issue: &GitlabIssue,
+    assignees: &[&GitlabProjectMember],
+    comment_template: Option<&str>,
+) -> Result<(), String> {
+    let assignee_ids: Vec<i32> = assignees.iter().map(|member| member.id).collect();
+    let res = send_with_retries(add_auth(
+        client
+            .put(format!(
+                "{}/api/v4/projects/{}/issues/{}",
+                gitlab_domain, issue.project_id, issue.iid
+            ))
+            .query(&assignee_ids_query(&assignee_ids)),
+        token,
+    ))?;
+    check_status(res, "assign an issue")?;
 
-    let config_issues = config.get_array("issues");
-    let selected_issues = if let Ok(config_issues) = config_issues {
-        let config_issues: Vec<i64> = config_issues
-            .into_iter()
-            .map(|val| val.into_int().expect("provided issue id is not an int"))
-            .collect();
-        let selected_issues: Vec<&GitlabIssue> = issues
+    if let Some(comment_template) = comment_template {
+        let usernames: Vec<&str> = assignees
             .iter()
-            .filter(|issue| config_issues.contains(&(issue.iid as i64)))
+            .map(|member| member.username.as_str())
             .collect();
-        selected_issues
-    } else {
-        let selection_types = vec![
-            IssueSelectionType::Milestone,
-            IssueSelectionType::Range,
-            IssueSelectionType::Manual,
-            IssueSelectionType::Label,
-        ];
-
-        let selection_type_res = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select the way you want to select the issues:")
-            .items(&selection_types)
-            .interact()
-            .unwrap();
-
-        let selection_type = &selection_types[selection_type_res];
-
-        let selected_issues: Vec<&GitlabIssue> = match selection_type {
-            IssueSelectionType::Manual => {
-                let selection = MultiSelect::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Select all the issues that you want to use: ")
-                    .items(&issues)
-                    .interact()
-                    .unwrap();
+        let body = comment_template.replace("{username}", &usernames.join(", @"));
+        let res = try_send(add_auth(
+            client
+                .post(format!(
+                    "{}/api/v4/projects/{}/issues/{}/notes",
+                    gitlab_domain, issue.project_id, issue.iid
+                ))
+                .form(&[("body", body)]),
+            token,
+        ));
 
-                let selected_issues: Vec<&GitlabIssue> =
-                    selection.into_iter().map(|i| &issues[i]).collect();
+        if let Err(err) = res.and_then(|res| check_status(res, "comment on the issue")) {
+            eprintln!(
+                "Warning: failed to comment on issue #{}, it is still assigned: {}",
+                issue.iid, err
+            );
+        }
+    }
 
-                selected_issues
-            }
-            IssueSelectionType::Milestone => {
-                let mut milestones: Vec<&GitlabMilestone> = Vec::new();
-                issues.iter().for_each(|issue| {
-                    if let Some(milestone) = &issue.milestone {
-                        if !milestones.contains(&milestone) {
-                            milestones.push(milestone);
-                        }
-                    }
-                });
+    Ok(())
+}
 
-                if milestones.len() == 0 {
-                    eprintln!("no milestone with opened issue, aborting");
-                    process::exit(1);
-                }
+// "alice -> bob", so the preview shows what the roll changes
+fn assignees_change(
+    issue: &GitlabIssue,
+    members: &[&GitlabProjectMember],
+    assignees: &[usize],
+) -> String {
+    let before: Vec<&str> = issue
+        .assignees
+        .iter()
+        .map(|assignee| assignee.username.as_str())
+        .collect();
+    let after: Vec<&str> = assignees
+        .iter()
+        .map(|&member| members[member].username.as_str())
+        .collect();
 
-                let selection = MultiSelect::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Select all the milestones that you want to use: ")
-                    .items(&milestones)
-                    .interact()
-                    .unwrap();
+    if before.len() == after.len() && after.iter().all(|username| before.contains(username)) {
+        return format!("{} (unchanged)", before.join(", "));
+    }
 
-                let selected_milestones: Vec<&GitlabMilestone> =
-                    selection.into_iter().map(|i| milestones[i]).collect();
+    let before = if before.is_empty() {
+        "(unassigned)".to_string()
+    } else {
+        before.join(", ")
+    };
+    format!("{} -> {}", before, after.join(", "))
+}
 
-                let selected_issues: Vec<&GitlabIssue> = issues
+fn planned_roll(
+    scopes: &[Scope],
+    issues: &[&GitlabIssue],
+    members: &[&GitlabProjectMember],
+    assignements: &[Vec<usize>],
+) -> PlannedRoll {
+    PlannedRoll {
+        project_id: match scopes {
+            [Scope::Project(project)] => Some(project.id),
+            _ => None,
+        },
+        issues: issues
+            .iter()
+            .zip(assignements)
+            .map(|(issue, assignees)| PlannedIssue {
+                project_id: issue.project_id,
+                iid: issue.iid,
+                title: issue.title.clone(),
+                assignees: assignees
                     .iter()
-                    .filter(|issue| {
-                        issue.milestone.is_some()
-                            && selected_milestones.contains(&issue.milestone.as_ref().unwrap())
+                    .map(|&member| PlannedAssignee {
+                        id: members[member].id,
+                        username: members[member].username.clone(),
                     })
-                    .collect();
+                    .collect(),
+            })
+            .collect(),
+    }
+}
 
-                selected_issues
-            }
-            IssueSelectionType::Range => {
-                let range_start = issue_id_select(&issues, "Enter the ID of the first issue:");
-                let range_end = issue_id_select(&issues, "Enter the ID of the last issue:");
+fn print_plan_json(
+    scopes: &[Scope],
+    issues: &[&GitlabIssue],
+    members: &[&GitlabProjectMember],
+    assignements: &[Vec<usize>],
+) {
+    let plan = planned_roll(scopes, issues, members, assignements);
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&plan).expect("failed to serialize the plan")
+    );
+}
 
-                let selected_issues: Vec<&GitlabIssue> = issues
-                    .iter()
-                    .filter(|issue| issue.iid >= range_start && issue.iid <= range_end)
-                    .collect();
-                selected_issues
-            }
-            IssueSelectionType::Label => {
-                let mut labels: HashSet<String> = HashSet::new();
-                issues.iter().for_each(|issue| {
-                    labels.extend(issue.labels.clone());
-                });
+// the issues are already assigned at this point, so a failure is only a warning
+fn post_webhook(client: &Client, url: &str, plan: &PlannedRoll) {
+    let body = serde_json::to_string(plan).expect("failed to serialize the plan");
+    let request = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body);
+    match try_send(request) {
+        Ok(res) if res.status().is_success() => info!("webhook {} notified", url),
+        Ok(res) => eprintln!(
+            "Warning: the webhook {} responded with {}",
+            url,
+            res.status()
+        ),
+        Err(err) => eprintln!("Warning: failed to reach the webhook {}: {}", url, err),
+    }
+}
 
-                if labels.len() == 0 {
-                    eprintln!("no label with opened issue, aborting");
-                    process::exit(1);
-                }
+// per member totals, aligned so they can be read at a glance
+fn print_summary(
+    issue_count: usize,
+    members: &[&GitlabProjectMember],
+    assignements: &[Vec<usize>],
+    weights: Option<(&[i32], &str)>,
+) {
+    let mut counts = vec![0; members.len()];
+    let mut member_weights = vec![0; members.len()];
+    for (i, assignees) in assignements.iter().enumerate() {
+        for &member in assignees {
+            counts[member] += 1;
+            if let Some((weights, _)) = weights {
+                member_weights[member] += weights[i];
+            }
+        }
+    }
 
-                let labels: Vec<&String> = labels.iter().collect();
-                let selection = MultiSelect::with_theme(&ColorfulTheme::default())
-                    .with_prompt("Select all the labels that you want to use: ")
-                    .items(&labels)
-                    .interact()
-                    .unwrap();
+    let names: Vec<String> = members.iter().map(|member| member.to_string()).collect();
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
 
-                let selected_labels: Vec<String> =
-                    selection.into_iter().map(|i| labels[i].clone()).collect();
+    println!();
+    println!("{} issues across {} members:", issue_count, members.len());
+    // the balanced modes do not split by count, so only the others get this note
+    let slot_count: usize = counts.iter().sum();
+    let rest = slot_count % members.len();
+    if weights.is_none() && rest != 0 {
+        println!(
+            "({} {} will receive an extra issue)",
+            rest,
+            if rest == 1 { "member" } else { "members" }
+        );
+    }
+    for (member, name) in names.iter().enumerate() {
+        let issues = if counts[member] == 1 {
+            "issue"
+        } else {
+            "issues"
+        };
+        if let Some((_, weight_name)) = weights {
+            println!(
+                "\t{:<width$}  {:>3} {:<6}  (total {}: {})",
+                name, counts[member], issues, weight_name, member_weights[member]
+            );
+        } else {
+            println!("\t{:<width$}  {:>3} {}", name, counts[member], issues);
+        }
+    }
 
-                let selected_issues: Vec<&GitlabIssue> = issues
-                    .iter()
-                    .filter(|issue| issue.labels.iter().any(|l| selected_labels.contains(&l)))
-                    .collect();
+    // easy to miss in a long summary when there are fewer issues than members
+    let idle: Vec<&str> = members
+        .iter()
+        .zip(&counts)
+        .filter(|(_, &count)| count == 0)
+        .map(|(member, _)| member.username.as_str())
+        .collect();
+    if !idle.is_empty() {
+        println!();
+        println!("No issues assigned to: {}", idle.join(", "));
+    }
+}
 
-                selected_issues
-            }
-        };
-        selected_issues
-    };
+// one line per issue, the titles are cut to fit in the terminal
+fn print_preview_table(
+    issues: &[&GitlabIssue],
+    members: &[&GitlabProjectMember],
+    assignements: &[Vec<usize>],
+) {
+    let ids: Vec<String> = issues
+        .iter()
+        .map(|issue| format!("#{}", issue.iid))
+        .collect();
+    let changes: Vec<String> = issues
+        .iter()
+        .zip(assignements)
+        .map(|(issue, assignees)| assignees_change(issue, members, assignees))
+        .collect();
+    // the headers can be wider than the values
+    let id_width = ids
+        .iter()
+        .map(|id| id.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Issue".len());
+    let change_width = changes
+        .iter()
+        .map(|change| change.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Assignees".len());
 
-    let config_members = config.get_array("members");
+    let (_, terminal_width) = console::Term::stdout().size();
+    let title_width = (terminal_width as usize)
+        .saturating_sub(id_width + change_width + 4)
+        .max(10);
 
-    let selected_members = if let Ok(config_members) = config_members {
-        let config_members: Vec<String> = config_members
-            .into_iter()
-            .map(|val| {
-                val.into_string()
-                    .expect("provided member username is not a string")
-            })
-            .collect();
-        let selected_members: Vec<&GitlabProjectMember> = members
-            .iter()
-            .filter(|member| config_members.contains(&(member.username)))
-            .collect();
-        selected_members
-    } else {
-        let selected_members = MultiSelect::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select all the members you want to asign the issues to:")
-            .items(&members)
-            .interact()
-            .unwrap();
-        let selected_members: Vec<&GitlabProjectMember> =
-            selected_members.into_iter().map(|i| &members[i]).collect();
-        selected_members
-    };
+    println!(
+        "{:<id_width$}  {:<title_width$}  Assignees",
+        "Issue", "Title"
+    );
+    for (i, issue) in issues.iter().enumerate() {
+        let title = if issue.title.chars().count() > title_width {
+            let cut: String = issue.title.chars().take(title_width - 1).collect();
+            format!("{}…", cut)
+        } else {
+            issue.title.clone()
+        };
+        println!(
+            "{:<id_width$}  {:<title_width$}  {}",
+            ids[i], title, changes[i]
+        );
+    }
+}
 
-    let mut rng = rand::thread_rng();
-    // selected_issues.shuffle(&mut rng);
-    let issue_per_member = selected_issues.len() / selected_members.len();
-    let rest = selected_issues.len() % selected_members.len();
-    let mut assignements: Vec<usize> = (0..selected_members.len())
-        .flat_map(|i| (0..issue_per_member).map(move |_| i))
-        .collect();
-    for _ in 0..rest {
-        assignements.push(rng.gen_range(0..selected_members.len()));
+// the titles end up in the text of markdown links, where these would end the link or format it
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '[' | ']' | '(' | ')' | '*' | '_' | '`' | '<' | '>'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
-    assignements.shuffle(&mut rng);
+    escaped
+}
 
-    println!("");
-    for (i, issue) in selected_issues.iter().enumerate() {
-        let rand_member = selected_members[assignements[i]];
-        // animation
-        println!("{}", issue);
-        println!("\t{}", rand_member);
+// counts down on a single line before the issue is revealed
+fn suspense(issue: &GitlabIssue, delay_ms: u64) {
+    let mut stdout = io::stdout();
+    print!("{}", CursorHide);
+    let steps = 3;
+    for step in (1..=steps).rev() {
+        println!("Rolling for #{}{}", issue.iid, ".".repeat(steps - step + 1));
+        stdout.flush().unwrap();
+        thread::sleep(Duration::from_millis(delay_ms / steps as u64));
+        print!("{}{}", CursorUp(1), EraseLine);
     }
+    print!("{}", CursorShow);
+    stdout.flush().unwrap();
+}
 
-    let confirm = Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt("Do you want to confirm this assignment ?")
-        .interact()
-        .unwrap();
-    if !confirm {
-        println!("Exiting");
-        process::exit(0);
+// slot machine style reveal: cycles through the members before settling on the chosen one
+fn reveal_member(members: &[&GitlabProjectMember], chosen: usize, rng: &mut impl Rng) {
+    let spins = 12;
+    let mut stdout = io::stdout();
+    print!("{}", CursorHide);
+    let start = rng.gen_range(0..members.len());
+    for spin in 0..spins {
+        println!("\t{}", members[(start + spin) % members.len()]);
+        stdout.flush().unwrap();
+        thread::sleep(Duration::from_millis(30 + spin as u64 * 10));
+        print!("{}{}", CursorUp(1), EraseLine);
     }
+    println!("\t{}", members[chosen]);
+    print!("{}", CursorShow);
+    stdout.flush().unwrap();
+}
 
-    for (i, issue) in selected_issues.iter().enumerate() {
-        let rand_member = selected_members[assignements[i]];
-        let res = client
-            .put(format!(
-                "{}/api/v4/projects/{}/issues/{}?assignee_ids={}",
-                gitlab_domain, project.id, issue.iid, rand_member.id
-            ))
-            .header("PRIVATE-TOKEN", token.clone())
-            .send()
-            .expect("failed to execute request");
+// exact titles have to match exactly, the others only have to be part of the title
+fn select_milestones_by_title<'a>(
+    milestones: &[&'a GitlabMilestone],
+    exact_titles: &[String],
+    partial_titles: &[String],
+) -> Vec<&'a GitlabMilestone> {
+    let mut selected_milestones: Vec<&GitlabMilestone> = Vec::new();
+    let mut unknown_titles: Vec<&String> = Vec::new();
 
-        if !res.status().is_success() {
-            eprintln!(
-                "Failed to assign an issue : {} ({})",
-                res.status().canonical_reason().unwrap(),
-                res.status().as_str()
-            );
-            process::exit(1);
+    let exact = exact_titles.iter().map(|title| (title, true));
+    let partial = partial_titles.iter().map(|title| (title, false));
+    for (title, is_exact) in exact.chain(partial) {
+        let matching: Vec<&GitlabMilestone> = milestones
+            .iter()
+            .copied()
+            .filter(|milestone| {
+                if is_exact {
+                    &milestone.title == title
+                } else {
+                    milestone.title.contains(title.as_str())
+                }
+            })
+            .collect();
+
+        if matching.is_empty() {
+            unknown_titles.push(title);
+        }
+        for milestone in matching {
+            if !selected_milestones.contains(&milestone) {
+                selected_milestones.push(milestone);
+            }
         }
     }
 
-    println!("issues assigned !");
+    if !unknown_titles.is_empty() {
+        let mut message = "no milestone matches the following titles:".to_string();
+        for title in unknown_titles {
+            message.push_str(&format!("\n\t{}", title));
+        }
+        message.push_str("\navailable milestones:");
+        for milestone in milestones {
+            message.push_str(&format!("\n\t{}", milestone.title));
+        }
+        fail(EXIT_ERROR, message);
+    }
 
-    return Ok(());
+    selected_milestones
 }
 
-fn issue_id_select(issues: &Vec<GitlabIssue>, prompt: &str) -> i32 {
-    let issue_id = Input::with_theme(&ColorfulTheme::default())
+// the id has to be at least min_id, so that a range cannot end before it starts
+// None when left empty, to go back to the previous prompt
+fn issue_id_select(issues: &[GitlabIssue], prompt: &str, min_id: Option<i32>) -> Option<i32> {
+    let issue_id: String = Input::with_theme(&*theme())
         .with_prompt(prompt)
+        .allow_empty(true)
         .validate_with(|input: &String| {
+            if input.is_empty() {
+                return Ok(());
+            }
             let num = input.parse::<i32>();
             match num {
+                Ok(num) if min_id.is_some_and(|min_id| num < min_id) => {
+                    Err("The last issue cannot be before the first one")
+                }
                 Ok(num) => {
                     let issue = issues.iter().find(|issue| issue.iid == num);
                     match issue {
@@ -467,10 +4325,216 @@ fn issue_id_select(issues: &Vec<GitlabIssue>, prompt: &str) -> i32 {
                 Err(_) => Err("Input is not a number"),
             }
         })
-        .interact()
+        .interact_text()
+        .unwrap();
+
+    if issue_id.is_empty() {
+        return None;
+    }
+    Some(issue_id.parse::<i32>().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn issue(iid: i32, weight: i32) -> GitlabIssue {
+        serde_json::from_value(json!({
+            "id": iid + 100,
+            "iid": iid,
+            "project_id": 1,
+            "title": format!("issue {}", iid),
+            "description": "",
+            "state": "opened",
+            "type": "ISSUE",
+            "assignees": [],
+            "milestone": null,
+            "labels": [],
+            "weight": weight,
+            "created_at": "2024-01-01T00:00:00Z",
+            "confidential": false,
+            "web_url": format!("https://gitlab.com/group/project/-/issues/{}", iid),
+            "iteration": null,
+        }))
+        .unwrap()
+    }
+
+    fn member(id: i32) -> GitlabProjectMember {
+        GitlabProjectMember {
+            id,
+            username: format!("member{}", id),
+            name: format!("Member {}", id),
+        }
+    }
+
+    fn milestone(id: i32, project_id: i32) -> GitlabMilestone {
+        serde_json::from_value(json!({
+            "id": id,
+            "project_id": project_id,
+            "group_id": null,
+            "title": "Sprint 1",
+            "description": "",
+            "state": "active",
+            "start_date": null,
+            "due_date": null,
+        }))
         .unwrap()
-        .parse::<i32>()
+    }
+
+    #[test]
+    fn milestones_of_different_projects_are_different() {
+        assert!(milestone(1, 10) == milestone(1, 10));
+        assert!(milestone(1, 10) != milestone(1, 11));
+        assert!(milestone(1, 10) != milestone(2, 10));
+    }
+
+    #[test]
+    fn duplicated_members_are_listed_once() {
+        // a direct and an inherited membership of the same user
+        let members: Vec<GitlabProjectMember> = serde_json::from_value(json!([
+            { "id": 1, "username": "alice", "name": "Alice", "access_level": 30 },
+            { "id": 2, "username": "bob", "name": "Bob", "access_level": 30 },
+            { "id": 1, "username": "alice", "name": "Alice", "access_level": 40 },
+        ]))
         .unwrap();
+        let usernames: Vec<String> = dedup_members(members)
+            .into_iter()
+            .map(|member| member.username)
+            .collect();
+        assert_eq!(usernames, ["alice", "bob"]);
+    }
+
+    #[test]
+    fn saving_a_profile_keeps_the_inline_ones() {
+        let path = env::temp_dir().join(format!("gitlab-roulette-{}.conf", process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "url = \"https://gitlab.com/a/b\"\nprofiles = { old = { members = [\"bob\"], mode = \"random\" } }\n").unwrap();
+        let profile = Profile {
+            members: vec!["alice".to_string()],
+            mode: "round-robin".to_string(),
+            ..Default::default()
+        };
+        // forced to toml even though the extension is not .toml
+        save_profile(path, ConfigFormat::Toml, "new", &profile).unwrap();
+        let document = fs::read_to_string(path)
+            .unwrap()
+            .parse::<toml_edit::DocumentMut>()
+            .unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(
+            document["profiles"]["old"]["members"][0].as_str(),
+            Some("bob")
+        );
+        assert_eq!(
+            document["profiles"]["new"]["members"][0].as_str(),
+            Some("alice")
+        );
+        assert_eq!(document["url"].as_str(), Some("https://gitlab.com/a/b"));
+    }
+
+    #[test]
+    fn saved_profiles_keep_the_selection_and_balancing() {
+        let path = env::temp_dir().join(format!("gitlab-roulette-{}.toml", process::id()));
+        let path = path.to_str().unwrap();
+        let profile = Profile {
+            members: vec!["alice".to_string()],
+            selection: Some("label".to_string()),
+            labels: Some(vec!["backend".to_string()]),
+            state: Some("all".to_string()),
+            unassigned_only: Some(true),
+            mode: "random".to_string(),
+            balance_by: Some("weight".to_string()),
+            ..Default::default()
+        };
+        save_profile(path, ConfigFormat::Toml, "sprint", &profile).unwrap();
+        let file_config = Config::builder()
+            .add_source(File::new(path, FileFormat::Toml))
+            .build()
+            .unwrap();
+        fs::remove_file(path).unwrap();
+
+        // loaded the same way as --profile does
+        let mut builder = Config::builder();
+        for (key, value) in file_config.get_table("profiles.sprint").unwrap() {
+            builder = builder.set_override(key, value).unwrap();
+        }
+        let config = builder.build().unwrap();
+        assert!(matches!(
+            config_enum(&config, "selection"),
+            Some(IssueSelectionType::Label)
+        ));
+        assert_eq!(
+            config_strings(&config, "labels"),
+            Some(vec!["backend".to_string()])
+        );
+        assert!(matches!(
+            config_enum(&config, "state"),
+            Some(IssueState::All)
+        ));
+        assert_eq!(config.get_bool("unassigned_only").ok(), Some(true));
+        assert_eq!(config_enum(&config, "balance_by"), Some(BalanceBy::Weight));
+    }
 
-    return issue_id;
+    #[test]
+    fn seeded_draws_give_the_same_plan() {
+        let issues: Vec<GitlabIssue> = (1..=9).map(|iid| issue(iid, iid % 3)).collect();
+        let issues: Vec<&GitlabIssue> = issues.iter().collect();
+        let members: Vec<GitlabProjectMember> = (1..=5).map(member).collect();
+        // the same members, listed in two different orders
+        let listed: Vec<&GitlabProjectMember> = members.iter().collect();
+        let shuffled: Vec<&GitlabProjectMember> =
+            [3, 0, 4, 1, 2].into_iter().map(|i| &members[i]).collect();
+        let weights: Vec<i32> = issues
+            .iter()
+            .map(|issue| issue.weight_or_default())
+            .collect();
+        let limits = MemberLimits { min: 0, max: None };
+
+        for (flags, balance_by) in [
+            (vec![], BalanceBy::Count),
+            (vec!["--assignees-per-issue", "2"], BalanceBy::Count),
+            (vec!["--limit-members", "3"], BalanceBy::Count),
+            (vec![], BalanceBy::Both),
+            (vec!["--avoid-current"], BalanceBy::Weight),
+        ] {
+            let cli = Cli::parse_from(
+                ["gitlab-roulette", "--seed", "42"]
+                    .into_iter()
+                    .chain(flags.iter().copied()),
+            );
+            let args = cli.roll;
+            let plan = |members: &[&GitlabProjectMember]| {
+                let mut rng = ChaCha8Rng::seed_from_u64(args.seed.unwrap());
+                let draw = draw_roll(
+                    &args,
+                    AssignMode::Random,
+                    balance_by,
+                    false,
+                    &weights,
+                    &issues,
+                    members.to_vec(),
+                    limits,
+                    &mut rng,
+                );
+                serde_json::to_string(&planned_roll(
+                    &[],
+                    &issues,
+                    &draw.members,
+                    &draw.assignements,
+                ))
+                .unwrap()
+            };
+            assert_eq!(plan(&listed), plan(&shuffled), "{:?}", flags);
+        }
+    }
+
+    #[test]
+    fn markdown_titles_are_escaped() {
+        assert_eq!(
+            escape_markdown(r"Fix [the] (api)_\ `x`"),
+            r"Fix \[the\] \(api\)\_\\ \`x\`"
+        );
+        assert_eq!(escape_markdown("Plain title"), "Plain title");
+    }
 }